@@ -0,0 +1,248 @@
+//! Typed values for the analog settings of the controller
+//!
+//! The waveforms in [`driver`](crate::driver) end with the gate voltage, source voltages and VCOM
+//! that [`set_lut`](crate::driver::Ssd1681::set_lut) writes together with the LUT. The types
+//! here encode those settings from millivolts, so they can be tuned with
+//! [`Ssd1681::set_gate_voltage`](crate::driver::Ssd1681::set_gate_voltage),
+//! [`set_source_voltages`](crate::driver::Ssd1681::set_source_voltages) and
+//! [`set_vcom`](crate::driver::Ssd1681::set_vcom) without editing a waveform. Voltages between
+//! two steps are rounded towards 0V.
+
+/// A value outside of the range the controller supports
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct OutOfRange {
+    /// Lowest accepted value, in millivolts for voltages
+    pub min:i32,
+    /// Highest accepted value, in millivolts for voltages
+    pub max:i32,
+}
+
+/// Checks that `mv` lies in `min..=max` and returns the number of `step`s above `min`
+const fn steps(mv:i32,min:i32,max:i32,step:i32)->Result<u8,OutOfRange> {
+    if mv<min||mv>max {
+        return Err(OutOfRange {min,max});
+    }
+    Ok(((mv-min)/step) as u8)
+}
+
+/// Gate driving voltage VGH (`GATE_VOLTAGE`): 10V to 20V in 0.5V steps
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct GateVoltage(u8);
+impl GateVoltage {
+    /// VGH from millivolts
+    pub const fn from_millivolts(mv:i32)->Result<Self,OutOfRange> {
+        match steps(mv,10_000,20_000,500) {
+            Ok(steps)=>Ok(GateVoltage(0x03+steps)),
+            Err(e)=>Err(e),
+        }
+    }
+    /// The voltage in millivolts
+    pub const fn millivolts(&self)->i32 {10_000+(self.0 as i32-0x03)*500}
+    /// Register value
+    pub const fn code(&self)->u8 {self.0}
+}
+
+/// Positive source driving voltage VSH1 or VSH2: 2.4V to 8.8V in 0.1V steps and 9V to 17V in
+/// 0.2V steps
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct Vsh(u8);
+impl Vsh {
+    /// VSH from millivolts. Values between 8.8V and 9V are rounded down to 8.8V.
+    pub const fn from_millivolts(mv:i32)->Result<Self,OutOfRange> {
+        if mv<2_400||mv>17_000 {
+            return Err(OutOfRange {min:2_400,max:17_000});
+        }
+        if mv>=9_000 {
+            Ok(Vsh(0x23+((mv-9_000)/200) as u8))
+        } else {
+            let mv=if mv>8_800 {8_800} else {mv};
+            Ok(Vsh(0x8E+((mv-2_400)/100) as u8))
+        }
+    }
+    /// The voltage in millivolts
+    pub const fn millivolts(&self)->i32 {
+        if self.0>=0x8E {
+            2_400+(self.0 as i32-0x8E)*100
+        } else {
+            9_000+(self.0 as i32-0x23)*200
+        }
+    }
+    /// Register value
+    pub const fn code(&self)->u8 {self.0}
+}
+
+/// Negative source driving voltage VSL: -9V to -17V in 0.5V steps
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct Vsl(u8);
+impl Vsl {
+    /// VSL from millivolts, e.g. `-15_000`
+    pub const fn from_millivolts(mv:i32)->Result<Self,OutOfRange> {
+        match steps(-mv,9_000,17_000,500) {
+            Ok(steps)=>Ok(Vsl(0x1A+steps*2)),
+            Err(_)=>Err(OutOfRange {min:-17_000,max:-9_000}),
+        }
+    }
+    /// The voltage in millivolts
+    pub const fn millivolts(&self)->i32 {-9_000-(self.0 as i32-0x1A)/2*500}
+    /// Register value
+    pub const fn code(&self)->u8 {self.0}
+}
+
+/// The three source driving voltages sent with `SOURCE_VOLTAGE`
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct SourceVoltages {
+    /// Drives black
+    pub vsh1:Vsh,
+    /// Drives red on three color panels
+    pub vsh2:Vsh,
+    /// Drives white
+    pub vsl:Vsl,
+}
+impl SourceVoltages {
+    pub(crate) fn codes(&self)->[u8;3] {[self.vsh1.code(),self.vsh2.code(),self.vsl.code()]}
+}
+
+/// VCOM (`WRITE_VCOM`): -0.2V to -3V in 0.1V steps
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct Vcom(u8);
+impl Vcom {
+    /// VCOM from millivolts, e.g. `-800`
+    pub const fn from_millivolts(mv:i32)->Result<Self,OutOfRange> {
+        match steps(-mv,200,3_000,100) {
+            Ok(steps)=>Ok(Vcom(0x08+steps*4)),
+            Err(_)=>Err(OutOfRange {min:-3_000,max:-200}),
+        }
+    }
+    /// VCOM from a register value, e.g. a sensed one. Only 0x08 to 0x78 in steps of 4 are valid.
+    #[cfg(feature="otp")]
+    pub(crate) const fn from_code(code:u8)->Result<Self,OutOfRange> {
+        if code<0x08||code>0x78||code&0b11!=0 {
+            return Err(OutOfRange {min:-3_000,max:-200});
+        }
+        Ok(Vcom(code))
+    }
+    /// The voltage in millivolts
+    pub const fn millivolts(&self)->i32 {-200-(self.0 as i32-0x08)/4*100}
+    /// Register value
+    pub const fn code(&self)->u8 {self.0}
+}
+
+/// Settings of one soft start phase of the booster
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct Phase {
+    strength:u8,
+    off_time:u8,
+}
+impl Phase {
+    /// `strength` is the driving strength (1 = weakest to 8), `min_off_time` the minimum off
+    /// time setting of GDR (4 = shortest to 15)
+    pub const fn new(strength:u8,min_off_time:u8)->Option<Self> {
+        if strength<1||strength>8||min_off_time<4||min_off_time>15 {
+            return None;
+        }
+        Some(Phase {strength,off_time:min_off_time})
+    }
+    /// Driving strength (1-8)
+    pub const fn strength(&self)->u8 {self.strength}
+    /// Minimum off time setting (4-15)
+    pub const fn min_off_time(&self)->u8 {self.off_time}
+    const fn code(&self)->u8 {0x80|(self.strength-1)<<4|self.off_time}
+}
+
+/// Duration of a soft start phase
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub enum PhaseDuration {
+    /// 10ms
+    Ms10,
+    /// 20ms
+    Ms20,
+    /// 30ms
+    Ms30,
+    /// 40ms
+    Ms40,
+}
+
+/// Booster soft start control (`BOOSTER_SOFT_START_CONTROL`)
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct SoftStart {
+    /// The three phases
+    pub phases:[Phase;3],
+    /// How long each phase lasts
+    pub durations:[PhaseDuration;3],
+}
+impl Default for SoftStart {
+    /// The controller's reset values
+    fn default()->Self {
+        SoftStart {
+            phases:[
+                Phase {strength:1,off_time:0xB},
+                Phase {strength:2,off_time:0xC},
+                Phase {strength:2,off_time:0x6},
+            ],
+            durations:[PhaseDuration::Ms40,PhaseDuration::Ms40,PhaseDuration::Ms10],
+        }
+    }
+}
+impl SoftStart {
+    /// The four bytes sent with `BOOSTER_SOFT_START_CONTROL`
+    pub fn codes(&self)->[u8;4] {
+        let [d1,d2,d3]=self.durations;
+        [
+            self.phases[0].code(),
+            self.phases[1].code(),
+            self.phases[2].code(),
+            (d3 as u8)<<4|(d2 as u8)<<2|d1 as u8,
+        ]
+    }
+}
+
+/// Level below which VCI detection reports a low supply
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub enum VciLevel {
+    /// 2.2V
+    V2_2=0x03,
+    /// 2.3V
+    V2_3=0x04,
+    /// 2.4V
+    V2_4=0x05,
+    /// 2.5V
+    V2_5=0x06,
+    /// 2.6V
+    V2_6=0x07,
+}
+impl VciLevel {
+    /// The level in millivolts
+    pub const fn millivolts(&self)->i32 {2_200+(*self as i32-0x03)*100}
+}
+
+/// Result of [`Ssd1681::check_supply`](crate::driver::Ssd1681::check_supply)
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct Supply {
+    /// The booster reached the driving voltages
+    pub hv_ready:bool,
+    /// VCI was at or above the threshold
+    pub vci_ok:bool,
+}
+impl Supply {
+    /// Returns `true` if the panel can be refreshed without producing garbage
+    pub fn refresh_safe(&self)->bool {self.hv_ready&&self.vci_ok}
+}
+
+/// Analog settings that replace the ones stored in the waveforms
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+pub(crate) struct Overrides {
+    pub(crate) gate:Option<GateVoltage>,
+    pub(crate) source:Option<SourceVoltages>,
+    pub(crate) vcom:Option<Vcom>,
+}
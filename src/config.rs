@@ -0,0 +1,212 @@
+//! Controller configuration applied by [`Ssd1681::new`](crate::driver::Ssd1681::new)
+
+use embedded_graphics_core::geometry::Size;
+use crate::flag;
+
+/// Gate scanning options sent with `DRIVER_CONTROL`
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+pub struct GateScan {
+    /// GD: the first gate output is G1 instead of G0
+    pub first_gate_g1:bool,
+    /// SM: scan the even gates before the odd ones (interlaced)
+    pub interlaced:bool,
+    /// TB: scan from the last gate to the first, flipping the image vertically
+    pub reverse:bool,
+}
+impl GateScan {
+    pub(crate) fn bits(&self)->u8 {
+        ((self.first_gate_g1 as u8)<<2)|((self.interlaced as u8)<<1)|self.reverse as u8
+    }
+}
+
+/// Which address the RAM address counter moves along first
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum AddressCounter {
+    /// Fill a row, then move to the next one
+    XFirst,
+    /// Fill a column of bytes, then move to the next one. The driver writes buffers row by row,
+    /// so [`Ssd1681::new`](crate::driver::Ssd1681::new) rejects it.
+    YFirst,
+}
+
+/// How the RAM address counter moves after each byte written to RAM (`DATA_ENTRY_MODE`)
+///
+/// The driver starts every RAM write in the corner the counter moves away from, so buffers are
+/// mirrored on the axes that decrement. Buffers are written row by row, so only
+/// [`AddressCounter::XFirst`] is supported, and decrementing X needs a panel width that is a
+/// multiple of 8; [`Ssd1681::new`](crate::driver::Ssd1681::new) fails with
+/// [`Error::UnsupportedDataEntry`](crate::driver::Error::UnsupportedDataEntry) otherwise.
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct DataEntryMode {
+    /// Increment X (move right) instead of decrementing it
+    pub x_increment:bool,
+    /// Increment Y (move down) instead of decrementing it
+    pub y_increment:bool,
+    /// Axis the counter moves along first
+    pub counter:AddressCounter,
+}
+impl Default for DataEntryMode {
+    fn default()->Self {
+        DataEntryMode {x_increment:true,y_increment:true,counter:AddressCounter::XFirst}
+    }
+}
+impl DataEntryMode {
+    pub(crate) fn bits(&self)->u8 {
+        let mut bits=0;
+        if self.x_increment {
+            bits|=flag::DATA_ENTRY_INCRX;
+        }
+        if self.y_increment {
+            bits|=flag::DATA_ENTRY_INCRY;
+        }
+        if self.counter==AddressCounter::YFirst {
+            bits|=flag::DATA_ENTRY_Y_FIRST;
+        }
+        bits
+    }
+}
+
+/// Waveform driven on the panel border (`BORDER_WAVEFORM_CONTROL`)
+///
+/// Set it during initialisation with [`Builder::border`] or at runtime with
+/// [`Ssd1681::set_border`](crate::driver::Ssd1681::set_border).
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub enum Border {
+    /// Follow the gray scale transition of LUT1
+    #[default]
+    FollowLut,
+    /// Follow the gray scale transition of LUT `n` (0-3, higher bits are ignored)
+    Lut(u8),
+    /// Like [`Border::Lut`], but drive VCOM instead of the LUT while the RED RAM bit is set
+    LutVcomOnRed(u8),
+    /// Hold the border at VSL, which shows as white
+    White,
+    /// Hold the border at VSH1, which shows as black
+    Black,
+    /// Hold the border at VSH2, which shows as red on three color panels
+    Red,
+    /// Hold the border at VSS (0V)
+    Vss,
+    /// Drive the border with VCOM
+    Vcom,
+    /// Leave the border floating (HiZ)
+    Floating,
+}
+impl Border {
+    pub(crate) fn bits(&self)->u8 {
+        match *self {
+            Border::FollowLut=>flag::BORDER_WAVEFORM_FOLLOW_LUT|flag::BORDER_WAVEFORM_LUT1,
+            Border::Lut(n)=>flag::BORDER_WAVEFORM_FOLLOW_LUT|(n&flag::BORDER_WAVEFORM_LUT_MASK),
+            Border::LutVcomOnRed(n)=>n&flag::BORDER_WAVEFORM_LUT_MASK,
+            Border::White=>flag::BORDER_WAVEFORM_FIX_LEVEL|flag::BORDER_WAVEFORM_VSL,
+            Border::Black=>flag::BORDER_WAVEFORM_FIX_LEVEL|flag::BORDER_WAVEFORM_VSH1,
+            Border::Red=>flag::BORDER_WAVEFORM_FIX_LEVEL|flag::BORDER_WAVEFORM_VSH2,
+            Border::Vss=>flag::BORDER_WAVEFORM_FIX_LEVEL|flag::BORDER_WAVEFORM_VSS,
+            Border::Vcom=>flag::BORDER_WAVEFORM_VCOM,
+            Border::Floating=>flag::BORDER_WAVEFORM_HIZ,
+        }
+    }
+    /// The border written as `bits`, `None` if reserved bits are set. [`Border::FollowLut`]
+    /// comes back as `Lut(1)`.
+    #[cfg(feature="trace")]
+    pub(crate) fn from_bits(bits:u8)->Option<Self> {
+        let lut=bits&flag::BORDER_WAVEFORM_LUT_MASK;
+        let level=bits&!flag::BORDER_WAVEFORM_HIZ;
+        Some(match bits&flag::BORDER_WAVEFORM_HIZ {
+            0 if bits&!(flag::BORDER_WAVEFORM_FOLLOW_LUT|flag::BORDER_WAVEFORM_LUT_MASK)!=0=>return None,
+            0 if bits&flag::BORDER_WAVEFORM_FOLLOW_LUT!=0=>Border::Lut(lut),
+            0=>Border::LutVcomOnRed(lut),
+            flag::BORDER_WAVEFORM_FIX_LEVEL=>match level {
+                flag::BORDER_WAVEFORM_VSS=>Border::Vss,
+                flag::BORDER_WAVEFORM_VSH1=>Border::Black,
+                flag::BORDER_WAVEFORM_VSL=>Border::White,
+                flag::BORDER_WAVEFORM_VSH2=>Border::Red,
+                _=>return None,
+            },
+            flag::BORDER_WAVEFORM_VCOM if level==0=>Border::Vcom,
+            flag::BORDER_WAVEFORM_HIZ if level==0=>Border::Floating,
+            _=>return None,
+        })
+    }
+}
+
+/// Source of the temperature used to pick the waveform (`TEMP_CONTROL`)
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+pub enum TemperatureSensor {
+    /// The controller's built in sensor
+    #[default]
+    Internal,
+    /// An external sensor on the controller's I2C bus
+    External,
+}
+impl TemperatureSensor {
+    pub(crate) fn bits(&self)->u8 {
+        match self {
+            TemperatureSensor::Internal=>flag::INTERNAL_TEMP_SENSOR,
+            TemperatureSensor::External=>flag::EXTERNAL_TEMP_SENSOR,
+        }
+    }
+}
+
+/// Settings sent to the controller during initialisation. Build one with [`Builder`].
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+pub struct Config {
+    pub(crate) gate_scan:GateScan,
+    pub(crate) data_entry:DataEntryMode,
+    pub(crate) border:Border,
+    pub(crate) temperature_sensor:TemperatureSensor,
+    pub(crate) panel_size:Option<Size>,
+}
+impl Config {
+    /// Gate scanning options
+    pub fn gate_scan(&self)->GateScan {self.gate_scan}
+    /// Data entry mode
+    pub fn data_entry(&self)->DataEntryMode {self.data_entry}
+    /// Border waveform
+    pub fn border(&self)->Border {self.border}
+    /// Temperature sensor
+    pub fn temperature_sensor(&self)->TemperatureSensor {self.temperature_sensor}
+    /// Size of the panel in pixels, `None` if it covers the controller's whole RAM
+    pub fn panel_size(&self)->Option<Size> {self.panel_size}
+}
+
+/// Builder for [`Config`]. Every setting defaults to what the driver used before it was
+/// configurable.
+#[derive(Copy,Clone,Debug,Default)]
+pub struct Builder {
+    config:Config,
+}
+impl Builder {
+    /// Starts from the default configuration
+    pub fn new()->Self {Self::default()}
+    /// Sets the gate scanning options
+    pub fn gate_scan(mut self,gate_scan:GateScan)->Self {
+        self.config.gate_scan=gate_scan;
+        self
+    }
+    /// Sets the data entry mode
+    pub fn data_entry(mut self,data_entry:DataEntryMode)->Self {
+        self.config.data_entry=data_entry;
+        self
+    }
+    /// Sets the border waveform
+    pub fn border(mut self,border:Border)->Self {
+        self.config.border=border;
+        self
+    }
+    /// Sets the temperature sensor
+    pub fn temperature_sensor(mut self,sensor:TemperatureSensor)->Self {
+        self.config.temperature_sensor=sensor;
+        self
+    }
+    /// Sets the size of the panel in pixels if it is smaller than the controller's RAM, e.g.
+    /// 122x250 for 2.13" panels on an SSD1680. The gate count and the RAM windows follow it and
+    /// buffers have `(width+7)/8` bytes per row.
+    pub fn panel_size(mut self,width:u32,height:u32)->Self {
+        self.config.panel_size=Some(Size::new(width,height));
+        self
+    }
+    /// Finishes the configuration
+    pub fn build(self)->Config {self.config}
+}
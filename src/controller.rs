@@ -0,0 +1,112 @@
+//! Differences between the controllers of the Solomon Systech SSD16xx family
+//!
+//! The command set in this driver is shared by the SSD1681, SSD1680, SSD1675B and SSD1608. What
+//! differs is the size of the RAM, how the gate count and RAM addresses are encoded, the length
+//! of the LUT and the display update sequences. Those details live in a [`Controller`], which is
+//! the last type parameter of [`Ssd1681`](crate::driver::Ssd1681) and defaults to
+//! [`controller::Ssd1681`](Ssd1681).
+//!
+//! The built in waveforms were made for the SSD1681. On the other controllers [`Controller::waveform`]
+//! returns `None`: every refresh uses the waveform the panel vendor stored in OTP and
+//! [`set_lut`](crate::driver::Ssd1681::set_lut) fails with
+//! [`Error::CustomLutUnsupported`](crate::driver::Error::CustomLutUnsupported) for anything but
+//! [`LutType::Full`].
+
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+use crate::{
+    driver::{LutType, Waveform, DIFFERENTIAL_WAVEFORM, FAST_WAVEFORM, FULL_WAVEFORM, GRAY4_WAVEFORM, PARTIAL_WAVEFORM},
+    flag,
+};
+
+/// Controller specific constants and encodings
+pub trait Controller {
+    /// Width of the RAM in pixels (number of source outputs)
+    const WIDTH:u32;
+    /// Height of the RAM in pixels (number of gate outputs)
+    const HEIGHT:u32;
+    /// Number of bytes sent with the `WRITE_LUT` command
+    const LUT_LEN:usize;
+    /// Number of bytes used for RAM X addresses (in units of 8 pixels)
+    const RAM_X_BYTES:usize=1;
+    /// Number of bytes used for RAM Y addresses
+    const RAM_Y_BYTES:usize=2;
+    /// Display update sequence that loads the LUT from OTP before refreshing
+    const UPDATE_OTP_LUT:u8=flag::BW_DISPLAY_MODE_1;
+    /// Display update sequence that refreshes with the LUT written by `WRITE_LUT`
+    const UPDATE_CUSTOM_LUT:u8=flag::GRAY4_DISPLAY_MODE_1;
+
+    /// Encodes the number of gates and the scanning flags (GD, SM, TB) for `DRIVER_CONTROL`
+    fn driver_control(gates:u16,scan:u8)->[u8;3] {
+        let mux=gates-1;
+        [mux as u8,(mux>>8) as u8,scan]
+    }
+    /// Returns the waveform (in SSD1681 layout) to load for `lut_type`, or `None` if the
+    /// controller should keep using its OTP waveform
+    fn waveform(_lut_type:LutType)->Option<&'static Waveform> {
+        None
+    }
+}
+
+/// Writes the low `bytes` bytes of `value` to `out` in little endian order
+pub(crate) fn encode_address(value:u32,bytes:usize,out:&mut [u8])->&[u8] {
+    out[..bytes].copy_from_slice(&value.to_le_bytes()[..bytes]);
+    &out[..bytes]
+}
+
+/// Clips `area` to a `width`x`height` RAM and widens it to whole bytes horizontally. Returns a
+/// zero sized rectangle if nothing is left.
+pub(crate) fn align_to_bytes(area:&Rectangle,width:u32,height:u32)->Rectangle {
+    let area=area.intersection(&Rectangle::new(Point::zero(),Size::new(width,height)));
+    if area.is_zero_sized() {
+        return Rectangle::zero();
+    }
+    let left=area.top_left.x&!7;
+    let right=(area.top_left.x+area.size.width as i32+7)&!7;
+    Rectangle::new(Point::new(left,area.top_left.y),Size::new((right-left) as u32,area.size.height))
+}
+
+/// SSD1681: 200x200, used by 1.54" panels
+pub struct Ssd1681;
+impl Controller for Ssd1681 {
+    const WIDTH:u32=200;
+    const HEIGHT:u32=200;
+    const LUT_LEN:usize=153;
+    fn waveform(lut_type:LutType)->Option<&'static Waveform> {
+        Some(match lut_type {
+            LutType::Full=>&FULL_WAVEFORM,
+            LutType::Partial=>&PARTIAL_WAVEFORM,
+            LutType::Gray4=>&GRAY4_WAVEFORM,
+            LutType::Fast=>&FAST_WAVEFORM,
+            LutType::Differential=>&DIFFERENTIAL_WAVEFORM,
+        })
+    }
+}
+
+/// SSD1680: 176x296, used by 2.13" and 2.9" panels
+pub struct Ssd1680;
+impl Controller for Ssd1680 {
+    const WIDTH:u32=176;
+    const HEIGHT:u32=296;
+    const LUT_LEN:usize=153;
+}
+
+/// SSD1675B: 160x296, used by 2.13" panels
+pub struct Ssd1675b;
+impl Controller for Ssd1675b {
+    const WIDTH:u32=160;
+    const HEIGHT:u32=296;
+    const LUT_LEN:usize=70;
+}
+
+/// SSD1608: 240x320, used by older 1.54" and 2.9" panels
+pub struct Ssd1608;
+impl Controller for Ssd1608 {
+    const WIDTH:u32=240;
+    const HEIGHT:u32=320;
+    const LUT_LEN:usize=30;
+    // The SSD1608 has no separate display mode 1, its pattern display step drives the panel.
+    const UPDATE_CUSTOM_LUT:u8=0xC4;
+}
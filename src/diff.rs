@@ -0,0 +1,199 @@
+//! Tracking what is on the panel and uploading only what changed
+//!
+//! [`DoubleBuffered`] wraps a [`Display`] together with a copy of the frame that was last
+//! uploaded. [`DoubleBuffered::diff`] compares the two byte by byte and yields the bounding
+//! rectangles of the changed rows, which [`DoubleBuffered::flush`] uploads with
+//! [`Ssd1681::update_area`] before refreshing with the current LUT, e.g. [`LutType::Partial`].
+//!
+//! [`LutType::Partial`]: crate::driver::LutType::Partial
+
+use core::{fmt::Debug, ops::{Deref, DerefMut}};
+use embedded_hal::{
+    spi::blocking::{Write, Transactional},
+    delay::blocking::DelayUs,
+    digital::blocking::{InputPin, OutputPin},
+};
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+use crate::{
+    controller::Controller,
+    driver::{Error, Ssd1681},
+    graphics::{Display, DefaultBuffer},
+};
+#[cfg(not(feature="alloc"))]
+use crate::graphics::BUFFER_LEN;
+
+/// Compares two frames of a `width` pixels wide panel, each given as buffer1 and buffer2, see
+/// [`Diff`]
+pub fn diff<'a>(previous:(&'a [u8],&'a [u8]),current:(&'a [u8],&'a [u8]),width:u32)->Diff<'a> {
+    Diff {previous:Some(previous),current,stride:(width as usize).div_ceil(8),row:0}
+}
+
+/// Iterator over the areas where two frames differ.
+///
+/// Every item is the bounding rectangle of a run of consecutive rows that contain changed
+/// bytes, in panel coordinates. X and the width are multiples of 8 as every byte holds 8
+/// pixels. Runs are separated by at least one unchanged row.
+#[derive(Clone,Debug)]
+pub struct Diff<'a> {
+    /// `None` if every byte counts as changed
+    previous:Option<(&'a [u8],&'a [u8])>,
+    current:(&'a [u8],&'a [u8]),
+    /// Bytes per row
+    stride:usize,
+    row:usize,
+}
+impl Diff<'_> {
+    /// Returns the first and last changed byte of `row`
+    fn changed_bytes(&self,row:usize)->Option<(usize,usize)> {
+        let stride=self.stride;
+        let start=row*stride;
+        let changed=|i:&usize|match self.previous {
+            Some(previous)=>{
+                let i=start+i;
+                previous.0[i]!=self.current.0[i]||previous.1[i]!=self.current.1[i]
+            },
+            None=>true,
+        };
+        let first=(0..stride).find(changed)?;
+        let last=(0..stride).rev().find(changed)?;
+        Some((first,last))
+    }
+}
+impl Iterator for Diff<'_> {
+    type Item=Rectangle;
+    fn next(&mut self)->Option<Rectangle> {
+        let rows=self.current.0.len()/self.stride;
+        let (top,(mut first,mut last))=loop {
+            if self.row>=rows {
+                return None;
+            }
+            self.row+=1;
+            if let Some(bytes)=self.changed_bytes(self.row-1) {
+                break (self.row-1,bytes);
+            }
+        };
+        while self.row<rows {
+            match self.changed_bytes(self.row) {
+                Some((row_first,row_last))=>{
+                    first=first.min(row_first);
+                    last=last.max(row_last);
+                    self.row+=1;
+                },
+                None=>break,
+            }
+        }
+        Some(Rectangle::new(
+            Point::new((first*8) as i32,top as i32),
+            Size::new(((last-first+1)*8) as u32,(self.row-top) as u32),
+        ))
+    }
+}
+
+/// A [`Display`] together with the frame that was last uploaded to the controller.
+///
+/// Draw into it like into the display itself, it dereferences to `D`.
+pub struct DoubleBuffered<D,B=DefaultBuffer> {
+    display:D,
+    previous:(B,B),
+    uploaded:bool,
+}
+impl<D:Display> DoubleBuffered<D> {
+    /// Wraps `display`. Nothing is known to be on the panel yet, so the first flush uploads the
+    /// whole frame.
+    ///
+    /// Without the `alloc` feature the uploaded frame is kept in arrays sized for a 200x200
+    /// panel; use [`with_buffers`](Self::with_buffers) for other panels.
+    pub fn new(display:D)->Self {
+        DoubleBuffered {
+            #[cfg(not(feature="alloc"))]
+            previous:([0;BUFFER_LEN],[0;BUFFER_LEN]),
+            #[cfg(feature="alloc")]
+            previous:(vec![0;display.buffer1().len()],vec![0;display.buffer2().len()]),
+            display,
+            uploaded:false,
+        }
+    }
+}
+impl<'a,D:Display> DoubleBuffered<D,&'a mut [u8]> {
+    /// Wraps `display`, keeping the uploaded frame in buffers provided by the caller
+    ///
+    /// # Panics
+    ///
+    /// If the buffers aren't as long as the display's.
+    pub fn with_buffers(display:D,previous1:&'a mut [u8],previous2:&'a mut [u8])->Self {
+        assert!(previous1.len()==display.buffer1().len()&&previous2.len()==display.buffer2().len(),"buffers don't match the display");
+        DoubleBuffered {display,previous:(previous1,previous2),uploaded:false}
+    }
+}
+impl<D:Display,B:AsRef<[u8]>+AsMut<[u8]>> DoubleBuffered<D,B> {
+    /// The wrapped display
+    pub fn display(&self)->&D {&self.display}
+    /// The wrapped display, mutably
+    pub fn display_mut(&mut self)->&mut D {&mut self.display}
+    /// Unwraps the display
+    pub fn into_inner(self)->D {self.display}
+    /// The frame that was last uploaded, if any
+    pub fn previous(&self)->Option<(&[u8],&[u8])> {
+        if self.uploaded {
+            Some((self.previous.0.as_ref(),self.previous.1.as_ref()))
+        } else {
+            None
+        }
+    }
+    /// Returns the areas that changed since the last upload. Before the first upload that is
+    /// the whole frame.
+    pub fn diff(&self)->Diff<'_> {
+        let current=self.display.buffers();
+        let stride=(self.display.panel_size().width as usize).div_ceil(8);
+        Diff {previous:self.previous(),current,stride,row:0}
+    }
+    /// Returns `true` if the display changed since the last upload
+    pub fn is_dirty(&self)->bool {
+        self.diff().next().is_some()
+    }
+    /// Remembers the current frame as the one on the panel, after uploading it some other way
+    pub fn mark_uploaded(&mut self) {
+        let (buffer1,buffer2)=self.display.buffers();
+        self.previous.0.as_mut().copy_from_slice(buffer1);
+        self.previous.1.as_mut().copy_from_slice(buffer2);
+        self.uploaded=true;
+    }
+    /// Forgets the uploaded frame, so the next flush uploads everything
+    pub fn invalidate(&mut self) {
+        self.uploaded=false;
+    }
+    /// Uploads the changed areas and refreshes the panel with the driver's current LUT.
+    ///
+    /// Returns the number of areas that were uploaded; nothing is refreshed if it is 0.
+    pub fn flush<SPI,BUSY,RST,DELAY,C>(&mut self,driver:&mut Ssd1681<SPI,BUSY,RST,DELAY,C>,spi:&mut SPI)->Result<usize,Error<SPI::Error>>
+    where
+        SPI: Write + Transactional,
+        BUSY: InputPin,
+        RST: OutputPin,
+        RST::Error: Debug,
+        DELAY: DelayUs,
+        C: Controller,
+    {
+        let mut areas=0;
+        for area in self.diff() {
+            driver.update_area(spi,&self.display,area)?;
+            areas+=1;
+        }
+        if areas>0 {
+            debug!("flush: {} areas",areas);
+            driver.display_window(spi)?;
+            self.mark_uploaded();
+        }
+        Ok(areas)
+    }
+}
+impl<D,B> Deref for DoubleBuffered<D,B> {
+    type Target=D;
+    fn deref(&self)->&D {&self.display}
+}
+impl<D,B> DerefMut for DoubleBuffered<D,B> {
+    fn deref_mut(&mut self)->&mut D {&mut self.display}
+}
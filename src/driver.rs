@@ -1,16 +1,20 @@
 //! Driver for interacting with SSD1681 display driver
 
 
-use core::fmt::Debug;
+use core::{fmt::Debug, marker::PhantomData};
 use embedded_hal::{
     spi::blocking::{Write, Transactional},
     delay::blocking::DelayUs,
     digital::blocking::{InputPin, OutputPin},
 };
-use embedded_graphics_core::primitives::Rectangle;
+use embedded_graphics_core::{geometry::{Point, Size}, primitives::Rectangle};
 use crate::{
     interface::DisplayInterface,
-    graphics::Display,
+    graphics::{Band, Display, DisplayRotation},
+    gray16::{self, Display1in54Gray16},
+    controller::{self, align_to_bytes, encode_address, Controller},
+    config::{AddressCounter, Border, Config, DataEntryMode},
+    analog::{GateVoltage, OutOfRange, Overrides, SoftStart, SourceVoltages, Supply, Vcom, VciLevel},
     cmd,
     flag,
     HEIGHT,
@@ -18,11 +22,13 @@ use crate::{
 };
 
 
-type Waveform=[u8;159];
+/// A waveform in SSD1681 layout: 153 bytes of LUT followed by the end option, gate voltage,
+/// 3 source voltages and VCOM.
+pub type Waveform=[u8;159];
 
 
 // The raw data from the C++ file
-static FULL_WAVEFORM:Waveform=[
+pub(crate) static FULL_WAVEFORM:Waveform=[
     0x80,0x48,0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
     0x40,0x48,0x80,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
     0x80,0x48,0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
@@ -43,7 +49,7 @@ static FULL_WAVEFORM:Waveform=[
     0x22,0x22,0x22,0x22,0x22,0x22,0x0,0x0,0x0,
     0x22,0x17,0x41,0x0,0x32,0x20
 ];
-static PARTIAL_WAVEFORM:Waveform=[
+pub(crate) static PARTIAL_WAVEFORM:Waveform=[
     0x0,0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
     0x80,0x80,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
     0x40,0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
@@ -67,7 +73,7 @@ static PARTIAL_WAVEFORM:Waveform=[
 // Taken from a good-display example using the SSD1681 and a B/W display
 // https://www.good-display.com/product/388.html
 // S-GDEY0154D67-210414.rar/S-GDEY0154D67-P-210414.rar/Display_EPD_W21.c
-static GRAY4_WAVEFORM:Waveform=[
+pub(crate) static GRAY4_WAVEFORM:Waveform=[
     0x40,0x48,0x80,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
     0x8,0x48,0x10,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
     0x2,0x48,0x4,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
@@ -88,10 +94,87 @@ static GRAY4_WAVEFORM:Waveform=[
     0x22,0x22,0x22,0x22,0x22,0x22,0x0,0x0,0x0,
     0x22,0x17,0x41,0x0,0x32,0x1C,
 ];
+// Drives every pixel to its black/white bit with the voltages of the partial waveform in a single
+// short phase, whatever the red bit
+pub(crate) static FAST_WAVEFORM:Waveform=[
+    0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x80,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x80,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0xA,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x22,0x22,0x22,0x22,0x22,0x22,0x0,0x0,0x0,
+    0x02,0x17,0x41,0xB0,0x32,0x28,
+];
+// Red RAM holds the previous image, black/white RAM the new one: pixels turning white (LUT1) or
+// black (LUT2) are driven, unchanged ones (LUT0, LUT3) are left alone
+pub(crate) static DIFFERENTIAL_WAVEFORM:Waveform=[
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x80,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x40,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0xF,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x0,0x0,0x0,0x0,0x0,0x0,0x0,
+    0x22,0x22,0x22,0x22,0x22,0x22,0x0,0x0,0x0,
+    0x02,0x17,0x41,0xB0,0x32,0x28,
+];
+
+
+/// Length of the user ID register
+pub const USER_ID_LEN:usize=10;
+
+/// The controller's status bit register
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct Status {
+    /// The booster reached the voltages set for driving the panel. Only valid after an HV ready
+    /// detection.
+    pub hv_ready:bool,
+    /// VCI was below the detection level. Only valid after a VCI detection.
+    pub vci_low:bool,
+    /// The controller is busy
+    pub busy:bool,
+    /// Chip ID (0-3)
+    pub chip_id:u8,
+}
+impl Status {
+    pub(crate) fn from_bits(bits:u8)->Self {
+        Status {
+            hv_ready:bits&flag::STATUS_HV_NOT_READY==0,
+            vci_low:bits&flag::STATUS_VCI_LOW!=0,
+            busy:bits&flag::STATUS_BUSY!=0,
+            chip_id:bits&flag::STATUS_CHIP_ID,
+        }
+    }
+}
 
 
 /// The refresh type. Full or partial.
 #[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+#[non_exhaustive]
 pub enum LutType {
     /// Full refresh
     Full,
@@ -103,70 +186,174 @@ pub enum LutType {
     /// From testing, it appears that you have to set the LUT to something other that Gray4, then
     /// update the 2 framebuffers, and finally reset the LUT to Gray4 to update the screen.
     /// NOTE: Gray4 has black/white bits inverted (for now), so you will need to update the display
-    /// accordingly
+    /// accordingly. [`Ssd1681::display_gray4`] does all of this in one call.
     /// -------------------
     /// **WARNING! EXPERIMENTAL AND MAY BREAK YOUR DISPLAY**
-    Gray4
+    Gray4,
+    /// Fast black/white refresh for animations and menus
+    ///
+    /// A single short phase drives every pixel straight to black or white, uses the same buffers
+    /// as `Full` and leaves ghosting behind. After
+    /// [`full_refresh_interval`](Ssd1681::set_full_refresh_interval) fast refreshes in a row the
+    /// next refresh is a full one.
+    /// -------------------
+    /// **WARNING!** Driving the panel without DC balance may damage it over time
+    Fast,
+    /// Transition waveform for [`Ssd1681::display_differential`]
+    ///
+    /// The red RAM has to hold the image on the panel and the black/white RAM the new one. Only
+    /// pixels that change are driven, so unchanged parts of the panel don't flicker.
+    Differential,
+}
+
+/// Fast refreshes done in a row before a full refresh is forced, see [`LutType::Fast`]
+pub const DEFAULT_FULL_REFRESH_INTERVAL:u32=10;
+/// Longest time in ms the driver waits for BUSY before failing with [`Error::BusyTimeout`]
+pub const DEFAULT_BUSY_TIMEOUT_MS:u32=10_000;
+
+
+/// Errors of driver operations that can fail for other reasons than the SPI bus
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[non_exhaustive]
+pub enum Error<E> {
+    /// The SPI bus returned an error
+    Spi(E),
+    /// The controller can't apply this rotation by itself
+    UnsupportedRotation(DisplayRotation),
+    /// The driver can't write buffers with this data entry mode
+    UnsupportedDataEntry(DataEntryMode),
+    /// The window has no pixels
+    EmptyWindow(Rectangle),
+    /// The window lies completely outside of the panel
+    WindowOutsidePanel(Rectangle),
+    /// The controller's LUT isn't in SSD1681 layout, so custom waveforms can't be loaded
+    CustomLutUnsupported,
+    /// BUSY stayed high for longer than the [busy timeout](Ssd1681::set_busy_timeout)
+    BusyTimeout,
+    /// The delay failed while waiting for a reset or BUSY
+    Delay,
+    /// The reset pin couldn't be driven
+    Reset,
+    /// A parameter or a value read from the controller lies outside of the supported range
+    OutOfRange(OutOfRange),
+    /// The configured panel is empty or larger than the controller's RAM
+    PanelSize(Size),
+    /// The buffer doesn't hold one frame of the panel, which takes the given number of bytes
+    BufferSize(usize),
+    /// The operation needs this LUT to be loaded with [`Ssd1681::set_lut`]
+    LutNotLoaded(LutType),
+    /// The operation needs the buffers of the display in their normal polarity
+    InvertedDisplay,
+}
+impl<E> From<E> for Error<E> {
+    fn from(error:E)->Self {Error::Spi(error)}
 }
 
 
 /// A configured display with a hardware interface.
-pub struct Ssd1681<SPI, BUSY, RST> {
-    interface: DisplayInterface<SPI, BUSY, RST>,
+///
+/// `C` selects the [`Controller`]; it defaults to the SSD1681.
+pub struct Ssd1681<SPI, BUSY, RST, DELAY, C = controller::Ssd1681> {
+    pub(crate) interface: DisplayInterface<SPI, BUSY, RST, DELAY>,
     window:Option<Rectangle>,
     lut_type:LutType,
+    config:Config,
+    rotation:DisplayRotation,
+    analog:Overrides,
+    fast_refreshes:u32,
+    full_refresh_interval:u32,
+    _controller:PhantomData<C>,
 }
-impl<SPI, BUSY, RST> Ssd1681<SPI, BUSY, RST>
+impl<SPI, BUSY, RST, DELAY> Ssd1681<SPI, BUSY, RST, DELAY>
 where
     SPI: Write + Transactional,
     BUSY: InputPin,
     RST: OutputPin,
     RST::Error: Debug,
+    DELAY: DelayUs,
 {
     /// Create and initialize the display driver
-    pub fn new<DELAY: DelayUs>(
+    ///
+    /// `delay` is kept for resets and for timing the waits for BUSY.
+    pub fn new(
         spi: &mut SPI,
         busy: BUSY,
         rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error>{
-        let interface = DisplayInterface::new(busy, rst);
-        let mut ssd1681 = Ssd1681 {interface,window:None,lut_type:LutType::Full};
-        ssd1681.init(spi,delay)?;
+        delay: DELAY,
+        config: Config,
+    ) -> Result<Self, Error<SPI::Error>>{
+        Self::with_controller(spi,busy,rst,delay,config)
+    }
+}
+impl<SPI, BUSY, RST, DELAY, C> Ssd1681<SPI, BUSY, RST, DELAY, C>
+where
+    SPI: Write + Transactional,
+    BUSY: InputPin,
+    RST: OutputPin,
+    RST::Error: Debug,
+    DELAY: DelayUs,
+    C: Controller,
+{
+    /// Create and initialize the display driver for another controller of the family.
+    ///
+    /// The controller is picked with the type, e.g. `Ssd1681<_,_,_,_,controller::Ssd1680>`.
+    pub fn with_controller(
+        spi: &mut SPI,
+        busy: BUSY,
+        rst: RST,
+        delay: DELAY,
+        config: Config,
+    ) -> Result<Self, Error<SPI::Error>>{
+        if let Some(panel)=config.panel_size {
+            if panel.width==0||panel.height==0||panel.width>C::WIDTH||panel.height>C::HEIGHT {
+                return Err(Error::PanelSize(panel));
+            }
+        }
+        // mirroring whole bytes would move the padding of the last byte onto the panel
+        let width=config.panel_size.map_or(C::WIDTH,|panel|panel.width);
+        if config.data_entry.counter!=AddressCounter::XFirst||!config.data_entry.x_increment&&width&7!=0 {
+            return Err(Error::UnsupportedDataEntry(config.data_entry));
+        }
+        let interface = DisplayInterface::new(busy, rst, delay);
+        let mut ssd1681 = Ssd1681 {interface,window:None,lut_type:LutType::Full,config,rotation:DisplayRotation::Rotate0,analog:Overrides::default(),fast_refreshes:0,full_refresh_interval:DEFAULT_FULL_REFRESH_INTERVAL,_controller:PhantomData};
+        ssd1681.init(spi)?;
         Ok(ssd1681)
     }
 
     /// Initialise the controller
-    fn init<DELAY: DelayUs>(
-        &mut self,
-        spi: &mut SPI,
-        delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.interface.reset(delay).unwrap();
+    fn init(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset()?;
+        debug!("init: software reset");
         self.interface.cmd(spi, cmd::SW_RESET)?;
-        self.interface.wait_until_idle();
+        let ms=self.interface.wait_until_idle()?;
+        trace!("init: reset finished after {} ms",ms);
 
-        self.interface.cmd_with_data(spi, cmd::DRIVER_CONTROL, &[(HEIGHT as u8) - 1, 0x00, 0x00])?;
+        debug!("init: driver output control");
+        let gate_scan=self.config.gate_scan.bits();
+        self.interface.cmd_with_data(spi, cmd::DRIVER_CONTROL, &C::driver_control(self.panel_size().height as u16, gate_scan))?;
 
         self.use_full_frame(spi)?;
 
-        self.interface.cmd_with_data(
-            spi,
-            cmd::BORDER_WAVEFORM_CONTROL,
-            &[flag::BORDER_WAVEFORM_FOLLOW_LUT | flag::BORDER_WAVEFORM_LUT1],
-        )?;
+        debug!("init: border waveform");
+        self.interface.cmd_with_data(spi, cmd::BORDER_WAVEFORM_CONTROL, &[self.config.border.bits()])?;
 
-        self.interface.cmd_with_data(spi, cmd::DATA_ENTRY_MODE, &[flag::DATA_ENTRY_INCRY_INCRX])?;
+        debug!("init: data entry mode");
+        self.interface.cmd_with_data(spi, cmd::DATA_ENTRY_MODE, &[self.data_entry().bits()])?;
 
-        self.interface.cmd_with_data(spi, cmd::TEMP_CONTROL, &[flag::INTERNAL_TEMP_SENSOR])?;
+        debug!("init: temperature sensor");
+        self.interface.cmd_with_data(spi, cmd::TEMP_CONTROL, &[self.config.temperature_sensor.bits()])?;
 
-        self.interface.wait_until_idle();
+        self.interface.wait_until_idle()?;
+        info!("init: done");
         Ok(())
     }
     /// Sets the current lookup table to `lut_type` and inverts the buffers if needed.
     ///
-    /// See [`LutType`] for caveats
-    pub fn set_lut<D:Display>(&mut self,spi:&mut SPI,lut_type:LutType,display:&mut D) -> Result<(), SPI::Error> {
+    /// Fails with [`Error::CustomLutUnsupported`], leaving the LUT and `display` alone, if the
+    /// controller has no waveform for `lut_type`. [`LutType::Full`] always works as it uses the
+    /// OTP waveform there. See [`LutType`] for caveats
+    pub fn set_lut<D:Display>(&mut self,spi:&mut SPI,lut_type:LutType,display:&mut D) -> Result<(),Error<SPI::Error>> {
+        Self::check_lut(lut_type)?;
         match self.lut_type {   // if we convert TO or FROM `Gray4` LutType, then invert the display
             LutType::Gray4=>{
                 if lut_type!=LutType::Gray4 {
@@ -179,130 +366,672 @@ where
                 }
             },
         }
-        let lut=match lut_type {
-            LutType::Full=>&FULL_WAVEFORM,
-            LutType::Partial=>&PARTIAL_WAVEFORM,
-            LutType::Gray4=>&GRAY4_WAVEFORM,
-        };
+        self.load_lut(spi,lut_type)
+    }
+    /// Fails if the controller has no waveform for `lut_type`, except for the OTP one for `Full`
+    fn check_lut(lut_type:LutType)->Result<(),Error<SPI::Error>> {
+        match (lut_type,C::waveform(lut_type)) {
+            (LutType::Full,_)|(_,Some(_))=>Ok(()),
+            _=>Err(Error::CustomLutUnsupported),
+        }
+    }
+    /// Loads the waveform for `lut_type` without touching any buffers
+    fn load_lut(&mut self,spi:&mut SPI,lut_type:LutType)->Result<(),Error<SPI::Error>> {
+        Self::check_lut(lut_type)?;
+        debug!("lut: {:?} -> {:?}",self.lut_type,lut_type);
         self.lut_type=lut_type;
+        let lut=match C::waveform(lut_type) {
+            Some(lut)=>lut,
+            None=>return Ok(()),    // `Full` keeps using the OTP waveform
+        };
+        self.load_waveform(spi,lut)
+    }
+    /// Writes `lut` to the LUT register, followed by its voltages or the ones set explicitly
+    fn load_waveform(&mut self,spi:&mut SPI,lut:&Waveform)->Result<(),Error<SPI::Error>> {
+        self.interface.cmd_with_data(spi,cmd::WRITE_LUT,&lut[..C::LUT_LEN])?;
+        self.interface.wait_until_idle()?;
 
-        self.interface.cmd_with_data(spi,0x32,&lut[..153])?;
-        self.interface.wait_until_idle();
-
-        self.interface.cmd_with_data(spi,0x3f,&[lut[153]])?;
-        self.interface.cmd_with_data(spi,0x03,&[lut[154]])?;
-        self.interface.cmd_with_data(spi,0x04,&lut[155..=157])?;
-        self.interface.cmd_with_data(spi,0x2C,&[lut[158]])?; // VCOM Voltage
+        // voltages set with `set_gate_voltage`, `set_source_voltages` and `set_vcom` win over the
+        // ones stored in the waveform
+        let gate=self.analog.gate.map_or(lut[154],|gate|gate.code());
+        let source=self.analog.source.map_or([lut[155],lut[156],lut[157]],|source|source.codes());
+        let vcom=self.analog.vcom.map_or(lut[158],|vcom|vcom.code());
+        self.interface.cmd_with_data(spi,cmd::END_OPTION,&[lut[153]])?;
+        self.interface.cmd_with_data(spi,cmd::GATE_VOLTAGE,&[gate])?;
+        self.interface.cmd_with_data(spi,cmd::SOURCE_VOLTAGE,&source)?;
+        self.interface.cmd_with_data(spi,cmd::WRITE_VCOM,&[vcom])?; // VCOM Voltage
 
-        self.interface.cmd(spi,0x2c)?;
-        self.interface.wait_until_idle();
+        self.interface.cmd(spi,cmd::WRITE_VCOM)?;
+        self.interface.wait_until_idle()?;
         return Ok(());
     }
     /// Returns the currently active lut type
     pub fn current_lut_type(&self)->LutType {self.lut_type}
+    /// Returns the configuration the controller was initialised with, updated by the setters
+    pub fn config(&self)->Config {self.config}
+    /// Returns the size of the panel: the one set with
+    /// [`Builder::panel_size`](crate::config::Builder::panel_size) or the controller's RAM size
+    pub fn panel_size(&self)->Size {
+        self.config.panel_size.unwrap_or(Size::new(C::WIDTH,C::HEIGHT))
+    }
+
+    /// Sets the longest time in ms to wait for BUSY before failing with [`Error::BusyTimeout`],
+    /// [`DEFAULT_BUSY_TIMEOUT_MS`] by default. 0 waits forever.
+    pub fn set_busy_timeout(&mut self,ms:u32) {self.interface.busy_timeout_ms=ms}
+    /// Returns the busy timeout in ms
+    pub fn busy_timeout(&self)->u32 {self.interface.busy_timeout_ms}
+
+    /// Sets how many [`LutType::Fast`] refreshes are done in a row before the next refresh is
+    /// forced to be a full one with the OTP waveform. 0 turns the safeguard off.
+    pub fn set_full_refresh_interval(&mut self,interval:u32) {self.full_refresh_interval=interval}
+    /// Fast refreshes done since the last full refresh
+    pub fn fast_refreshes(&self)->u32 {self.fast_refreshes}
+
+    /// Sets the waveform driven on the panel border. It takes effect with the next refresh.
+    pub fn set_border(&mut self,spi:&mut SPI,border:Border)->Result<(),Error<SPI::Error>> {
+        debug!("border: {:?}",border);
+        self.interface.cmd_with_data(spi,cmd::BORDER_WAVEFORM_CONTROL,&[border.bits()])?;
+        self.config.border=border;
+        Ok(())
+    }
+
+    /// Sets the gate driving voltage VGH. It is kept when the LUT changes.
+    pub fn set_gate_voltage(&mut self,spi:&mut SPI,voltage:GateVoltage)->Result<(),Error<SPI::Error>> {
+        debug!("gate voltage: {}mV",voltage.millivolts());
+        self.interface.cmd_with_data(spi,cmd::GATE_VOLTAGE,&[voltage.code()])?;
+        self.analog.gate=Some(voltage);
+        Ok(())
+    }
+    /// Sets the source driving voltages VSH1, VSH2 and VSL. They are kept when the LUT changes.
+    pub fn set_source_voltages(&mut self,spi:&mut SPI,voltages:SourceVoltages)->Result<(),Error<SPI::Error>> {
+        debug!(
+            "source voltages: vsh1={}mV vsh2={}mV vsl={}mV",
+            voltages.vsh1.millivolts(),voltages.vsh2.millivolts(),voltages.vsl.millivolts(),
+        );
+        self.interface.cmd_with_data(spi,cmd::SOURCE_VOLTAGE,&voltages.codes())?;
+        self.analog.source=Some(voltages);
+        Ok(())
+    }
+    /// Sets VCOM. It is kept when the LUT changes.
+    pub fn set_vcom(&mut self,spi:&mut SPI,vcom:Vcom)->Result<(),Error<SPI::Error>> {
+        debug!("vcom: {}mV",vcom.millivolts());
+        self.interface.cmd_with_data(spi,cmd::WRITE_VCOM,&[vcom.code()])?;
+        self.analog.vcom=Some(vcom);
+        Ok(())
+    }
+    /// Sets the soft start of the booster that generates the driving voltages
+    pub fn set_soft_start(&mut self,spi:&mut SPI,soft_start:SoftStart)->Result<(),Error<SPI::Error>> {
+        debug!("soft start: {:?}",soft_start);
+        Ok(self.interface.cmd_with_data(spi,cmd::BOOSTER_SOFT_START_CONTROL,&soft_start.codes())?)
+    }
+
+    /// Lets the controller rotate the image instead of the [`Display`].
+    ///
+    /// Buffers stay unrotated (keep the display at [`DisplayRotation::Rotate0`]): the data entry
+    /// mode is switched to count backwards from the opposite corner of the RAM and the bits of
+    /// every byte are reversed while uploading, so a 180° flip costs no redrawing. The RAM is
+    /// written 8 pixels of a row at a time, which rules out 90° and 270°, and 180° on panels whose
+    /// width isn't a multiple of 8; those return [`Error::UnsupportedRotation`] and still need
+    /// [`Display::set_rotation`].
+    pub fn set_rotation(&mut self,spi:&mut SPI,rotation:DisplayRotation)->Result<(),Error<SPI::Error>> {
+        match rotation {
+            DisplayRotation::Rotate0=>{},
+            // mirroring whole bytes would move the padding of the last byte onto the panel
+            DisplayRotation::Rotate180 if self.panel_size().width&7==0=>{},
+            _=>return Err(Error::UnsupportedRotation(rotation)),
+        }
+        debug!("rotation: {:?}",rotation);
+        self.rotation=rotation;
+        self.interface.cmd_with_data(spi,cmd::DATA_ENTRY_MODE,&[self.data_entry().bits()])?;
+        self.use_current_window(spi)
+    }
+    /// Returns the rotation applied by the controller
+    pub fn rotation(&self)->DisplayRotation {self.rotation}
+
+    /// Runs the HV ready and VCI detections and reports whether a refresh is safe.
+    ///
+    /// `threshold` is the lowest VCI a refresh should run with. The analog circuits are switched
+    /// on for the detections and off again afterwards.
+    pub fn check_supply(&mut self,spi:&mut SPI,threshold:VciLevel)->Result<Supply,Error<SPI::Error>> {
+        self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::CLOCK_ANALOG_ON])?;
+        self.interface.cmd(spi,cmd::MASTER_ACTIVATE)?;
+        self.interface.wait_until_idle()?;
+
+        // one detection loop with the shortest cool down
+        self.interface.cmd_with_data(spi,cmd::HV_READY_DETECTION,&[0x00])?;
+        self.interface.wait_until_idle()?;
+        let hv_ready=self.read_status(spi)?.hv_ready;
+
+        self.interface.cmd_with_data(spi,cmd::VCI_DETECTION,&[threshold as u8])?;
+        self.interface.wait_until_idle()?;
+        let vci_ok=!self.read_status(spi)?.vci_low;
+
+        self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::CLOCK_ANALOG_OFF])?;
+        self.interface.cmd(spi,cmd::MASTER_ACTIVATE)?;
+        self.interface.wait_until_idle()?;
+
+        let supply=Supply {hv_ready,vci_ok};
+        if !supply.refresh_safe() {
+            warn!("supply: hv ready {}, vci above {}mV {}",hv_ready,threshold.millivolts(),vci_ok);
+        }
+        Ok(supply)
+    }
+
+    /// Writes the user ID register. It is kept until the controller is powered off.
+    pub fn write_user_id(&mut self,spi:&mut SPI,id:&[u8;USER_ID_LEN])->Result<(),Error<SPI::Error>> {
+        debug!("user id: {:?}",id);
+        Ok(self.interface.cmd_with_data(spi,cmd::WRITE_USER_ID,id)?)
+    }
+    /// Reads the user ID register
+    pub fn read_user_id(&mut self,spi:&mut SPI)->Result<[u8;USER_ID_LEN],Error<SPI::Error>> {
+        let mut id=[0;USER_ID_LEN];
+        self.interface.cmd_read(spi,cmd::READ_USER_ID,&mut id)?;
+        Ok(id)
+    }
+    /// Reads the status bit register
+    pub fn read_status(&mut self,spi:&mut SPI)->Result<Status,Error<SPI::Error>> {
+        let mut bits=[0];
+        self.interface.cmd_read(spi,cmd::READ_STATUS,&mut bits)?;
+        let status=Status::from_bits(bits[0]);
+        trace!("status: {:?}",status);
+        Ok(status)
+    }
 
     /// Update buffer1 on the display driver
-    pub fn update_frame1(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
+    pub fn update_frame1(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(),Error<SPI::Error>> {
+        self.check_buffer(buffer)?;
         self.use_full_frame(spi)?;
-        self.interface
-            .cmd_with_data(spi, cmd::WRITE_BUFFER1_DATA, &buffer)
+        Ok(self.write_ram(spi, cmd::WRITE_BUFFER1_DATA, buffer)?)
     }
     /// Update buffer2 on the display driver
-    pub fn update_frame2(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
+    pub fn update_frame2(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(),Error<SPI::Error>> {
+        self.check_buffer(buffer)?;
         self.use_full_frame(spi)?;
-        self.interface
-            .cmd_with_data(spi, cmd::WRITE_BUFFER2_DATA, &buffer)
+        Ok(self.write_ram(spi, cmd::WRITE_BUFFER2_DATA, buffer)?)
     }
-    /// Takes a buffer implementing [`Display`] and updates the EPD's buffers with it
-    pub fn update_frames<D:Display>(&mut self,spi:&mut SPI,buffers:&D)->Result<(),SPI::Error> {
+    /// Takes a buffer implementing [`Display`] and updates the EPD's buffers with it.
+    ///
+    /// The `update_*` methods fail with [`Error::BufferSize`] before writing anything unless the
+    /// buffers have `(width+7)/8` bytes per row for every row of the panel.
+    pub fn update_frames<D:Display>(&mut self,spi:&mut SPI,buffers:&D)->Result<(),Error<SPI::Error>> {
         self.update_frame1(spi,buffers.buffer1())?;
         self.update_frame2(spi,buffers.buffer2())
     }
-    /// Sets the current memory window
-    pub fn set_window(&mut self,spi:&mut SPI,window:Rectangle)->Result<(),SPI::Error> {
-        self.window=Some(window.clone());
-        self.use_window(spi,window)
+    /// Writes the part of the buffers inside `area` to both RAMs and leaves the rest of the RAM
+    /// alone.
+    ///
+    /// `area` is in panel coordinates, is clipped to the panel and widened to whole bytes
+    /// horizontally. The window set with [`set_window`](Self::set_window) is restored afterwards.
+    pub fn update_area<D:Display>(&mut self,spi:&mut SPI,buffers:&D,area:Rectangle)->Result<(),Error<SPI::Error>> {
+        self.check_buffer(buffers.buffer1())?;
+        self.check_buffer(buffers.buffer2())?;
+        let area=self.align(&area);
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        debug!(
+            "update area: x={} y={} width={} height={}",
+            area.top_left.x,area.top_left.y,area.size.width,area.size.height,
+        );
+        self.write_ram_area(spi,cmd::WRITE_BUFFER1_DATA,buffers.buffer1(),area)?;
+        self.write_ram_area(spi,cmd::WRITE_BUFFER2_DATA,buffers.buffer2(),area)?;
+        self.use_current_window(spi)
+    }
+    /// Shows `display` by refreshing only the pixels that differ from the image in the red RAM.
+    ///
+    /// The black/white buffer is written to the black/white RAM and the panel is refreshed with
+    /// [`LutType::Differential`], which has to be loaded with [`set_lut`](Self::set_lut) first;
+    /// otherwise this fails with [`Error::LutNotLoaded`], and with [`Error::InvertedDisplay`] if
+    /// `display` is inverted, before anything is written. The SSD1681 can't copy one RAM to the
+    /// other, so the black/white buffer is then written to the red RAM too, leaving it as the
+    /// previous image for the next call. After [`update_frames`](Self::update_frames) with a
+    /// black and white `display` both RAMs already hold the same image, so a full refresh is a
+    /// good starting point.
+    pub fn display_differential<D:Display>(&mut self,spi:&mut SPI,display:&D)->Result<(),Error<SPI::Error>> {
+        let panel=Rectangle::new(Point::zero(),self.panel_size());
+        self.display_differential_area(spi,display,panel)
+    }
+    /// Like [`display_differential`](Self::display_differential), but only writes the part of
+    /// the buffer inside `area` to both RAMs. `area` is in panel coordinates and is aligned like
+    /// in [`update_area`](Self::update_area); nothing is refreshed if it is empty.
+    pub fn display_differential_area<D:Display>(&mut self,spi:&mut SPI,display:&D,area:Rectangle)->Result<(),Error<SPI::Error>> {
+        if self.lut_type!=LutType::Differential {
+            return Err(Error::LutNotLoaded(LutType::Differential));
+        }
+        if display.is_inverted() {
+            return Err(Error::InvertedDisplay);
+        }
+        self.check_buffer(display.buffer1())?;
+        let area=self.align(&area);
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        debug!(
+            "differential: x={} y={} width={} height={}",
+            area.top_left.x,area.top_left.y,area.size.width,area.size.height,
+        );
+        self.write_ram_area(spi,cmd::WRITE_BUFFER1_DATA,display.buffer1(),area)?;
+        self.display_frame(spi)?;
+        self.write_ram_area(spi,cmd::WRITE_BUFFER2_DATA,display.buffer1(),area)?;
+        self.use_current_window(spi)
+    }
+    /// Like [`update_area`](Self::update_area), but with `area` in the rotated coordinates
+    /// `display` is drawn in
+    pub fn update_logical_area<D:Display>(&mut self,spi:&mut SPI,display:&D,area:Rectangle)->Result<(),Error<SPI::Error>> {
+        let panel=self.panel_size();
+        self.update_area(spi,display,display.rotation().to_panel(&area,panel))
+    }
+    /// Sets the current memory window.
+    ///
+    /// `window` is in panel coordinates. It is clipped to the panel and widened to whole bytes
+    /// horizontally, [`current_window`](Self::current_window) returns the result. Fails with
+    /// [`Error::EmptyWindow`] or [`Error::WindowOutsidePanel`] if no pixel is left, leaving the
+    /// previous window in place.
+    pub fn set_window(&mut self,spi:&mut SPI,window:Rectangle)->Result<(),Error<SPI::Error>> {
+        if window.is_zero_sized() {
+            return Err(Error::EmptyWindow(window));
+        }
+        let aligned=self.align(&window);
+        if aligned.is_zero_sized() {
+            return Err(Error::WindowOutsidePanel(window));
+        }
+        debug!(
+            "window: x={} y={} width={} height={}",
+            aligned.top_left.x,aligned.top_left.y,aligned.size.width,aligned.size.height,
+        );
+        self.window=Some(aligned);
+        self.use_window(spi,aligned)
+    }
+    /// Sets the current memory window from `window` in the rotated coordinates `display` is drawn
+    /// in, see [`set_window`](Self::set_window)
+    pub fn set_logical_window<D:Display>(&mut self,spi:&mut SPI,display:&D,window:Rectangle)->Result<(),Error<SPI::Error>> {
+        let panel=self.panel_size();
+        self.set_window(spi,display.rotation().to_panel(&window,panel))
     }
     /// Unsets the current window
-    pub fn unset_window(&mut self,spi:&mut SPI)->Result<(),SPI::Error> {
+    pub fn unset_window(&mut self,spi:&mut SPI)->Result<(),Error<SPI::Error>> {
+        debug!("window: full frame");
         self.window=None;
         self.use_full_frame(spi)
     }
     /// Gets the current window, if there is one
     pub fn current_window(&self)->Option<Rectangle> {self.window}
     /// Displays part of the buffer
-    pub fn display_window(&mut self,spi:&mut SPI)->Result<(),SPI::Error> {
-        match self.lut_type {
-            LutType::Gray4=>self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::GRAY4_DISPLAY_MODE_1])?,
-            _=>self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::BW_DISPLAY_MODE_1])?,
-        }
+    pub fn display_window(&mut self,spi:&mut SPI)->Result<(),Error<SPI::Error>> {
+        let control=self.next_update_control();
+        self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[control])?;
 
+        debug!("refresh window: start ({:?})",self.lut_type);
         self.interface.cmd(spi, cmd::MASTER_ACTIVATE)?;
 
-        self.interface.wait_until_idle();
-        return Ok(());
+        let ms=self.interface.wait_until_idle()?;
+        debug!("refresh window: finished after {} ms",ms);
+        self.reload_fast_lut(spi,control)
     }
     /// Start an update of the whole display
-    pub fn display_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
-        self.use_full_frame(spi)?;
-        match self.lut_type {
-            LutType::Gray4=>self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::GRAY4_DISPLAY_MODE_1])?,
-            _=>self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::BW_DISPLAY_MODE_1])?,
+    pub fn display_frame(&mut self, spi: &mut SPI) -> Result<(),Error<SPI::Error>> {
+        debug!("refresh: start ({:?})",self.lut_type);
+        let control=self.next_update_control();
+        self.refresh(spi,control)?;
+        self.reload_fast_lut(spi,control)
+    }
+    /// Loads the `Fast` waveform again after a forced full refresh with `control` replaced it by
+    /// the OTP one
+    fn reload_fast_lut(&mut self,spi:&mut SPI,control:u8)->Result<(),Error<SPI::Error>> {
+        if self.lut_type==LutType::Fast&&control==C::UPDATE_OTP_LUT {
+            debug!("fast: reloading the waveform after a full refresh");
+            self.load_lut(spi,LutType::Fast)?;
         }
+        Ok(())
+    }
+    /// Refreshes the whole panel with the display update sequence `control`
+    fn refresh(&mut self,spi:&mut SPI,control:u8)->Result<(),Error<SPI::Error>> {
+        self.use_full_frame(spi)?;
+        self.interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[control])?;
         self.interface.cmd(spi,cmd::MASTER_ACTIVATE)?;
 
-        self.interface.wait_until_idle();
+        let ms=self.interface.wait_until_idle()?;
+        debug!("refresh: finished after {} ms",ms);
 
         Ok(())
     }
 
+    /// Shows `display` in four grays using the [`LutType::Gray4`] waveform.
+    ///
+    /// Both RAMs are written while another LUT is loaded and the `Gray4` LUT is only loaded for
+    /// the refresh. The bits are inverted on the way unless `display` already is, e.g. by
+    /// [`set_lut`](Self::set_lut), so it can be drawn with `color::{WHITE, LGRAY, DGRAY, BLACK}`
+    /// as usual. The previous LUT and the window are restored afterwards.
+    ///
+    /// Fails with [`Error::CustomLutUnsupported`] before writing anything if the controller has
+    /// no `Gray4` waveform.
+    ///
+    /// **WARNING! EXPERIMENTAL AND MAY BREAK YOUR DISPLAY**
+    pub fn display_gray4<D:Display>(&mut self,spi:&mut SPI,display:&D)->Result<(),Error<SPI::Error>> {
+        Self::check_lut(LutType::Gray4)?;
+        let previous=self.lut_type;
+        debug!("gray4: upload and refresh");
+        if previous==LutType::Gray4 {
+            self.load_lut(spi,LutType::Full)?;
+        }
+        self.check_buffer(display.buffer1())?;
+        self.check_buffer(display.buffer2())?;
+        let inverted=display.is_inverted();
+        self.use_full_frame(spi)?;
+        for (command,buffer) in [(cmd::WRITE_BUFFER1_DATA,display.buffer1()),(cmd::WRITE_BUFFER2_DATA,display.buffer2())] {
+            if inverted {
+                self.write_ram(spi,command,buffer)?;
+            } else {
+                self.write_ram_inverted(spi,command,buffer)?;
+            }
+        }
+        self.load_lut(spi,LutType::Gray4)?;
+        self.display_frame(spi)?;
+        if previous!=LutType::Gray4 {
+            self.load_lut(spi,previous)?;
+        }
+        self.use_current_window(spi)
+    }
+
+    /// Shows `display` in 16 grays, see the [`gray16`] module.
+    ///
+    /// `frames` are the frames each pass drives towards white, from the lowest bit of the luma
+    /// to the highest, e.g. [`gray16::DEFAULT_PASS_FRAMES`]. Passes with 0 frames are skipped.
+    /// The panel is cleared to black first, which leaves the red RAM black and the last pass in
+    /// the black/white RAM. The previous LUT and the window are restored afterwards.
+    ///
+    /// Fails with [`Error::CustomLutUnsupported`] on controllers that don't drive a 200x200
+    /// panel or have no SSD1681 waveforms.
+    ///
+    /// **WARNING! EXPERIMENTAL AND MAY BREAK YOUR DISPLAY**
+    pub fn display_gray16<B:AsRef<[u8]>+AsMut<[u8]>>(
+        &mut self,
+        spi:&mut SPI,
+        display:&Display1in54Gray16<B>,
+        frames:&[u8;4],
+    )->Result<(),Error<SPI::Error>> {
+        if self.panel_size()!=Size::new(WIDTH as u32,HEIGHT as u32)||C::waveform(LutType::Full).is_none() {
+            return Err(Error::CustomLutUnsupported);
+        }
+        info!("gray16: {} passes",frames.iter().filter(|&&frames|frames>0).count());
+        self.fill_frames(spi,0)?;
+        self.refresh(spi,C::UPDATE_OTP_LUT)?;
+        for (bit,&frames) in frames.iter().enumerate() {
+            if frames==0 {
+                continue;
+            }
+            debug!("gray16: pass {} for {} frames",bit,frames);
+            self.load_waveform(spi,&gray16::pass_waveform(frames))?;
+            self.use_full_frame(spi)?;
+            self.interface.cmd(spi,cmd::WRITE_BUFFER1_DATA)?;
+            let mut row=[0;WIDTH/8];
+            for y in 0..HEIGHT {
+                for (i,byte) in row.iter_mut().enumerate() {
+                    *byte=(0..8).fold(0,|byte,x|byte<<1|(display.luma(i*8+x,y)>>bit)&1);
+                }
+                self.write_ram_data(spi,&row)?;
+            }
+            self.refresh(spi,C::UPDATE_CUSTOM_LUT)?;
+        }
+        self.load_lut(spi,self.lut_type)?;
+        self.use_current_window(spi)
+    }
+
     /// Make the whole black and white frame on the display driver white
-    pub fn clear_frame1(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    pub fn clear_frame1(&mut self, spi: &mut SPI) -> Result<(),Error<SPI::Error>> {
         self.use_full_frame(spi)?;
 
         let color=match self.lut_type {
-            LutType::Gray4=>0xff,
-            _=>0,
+            LutType::Gray4=>0,
+            _=>0xff,
         };
 
         self.interface.cmd(spi, cmd::WRITE_BUFFER1_DATA)?;
         self.interface
-            .data_x_times(spi, color, (WIDTH as u32) / 8 * (HEIGHT as u32))?;
+            .data_x_times(spi, color, self.frame_len())?;
         Ok(())
     }
     /// Make the whole black and white frame on the display driver white
-    pub fn clear_frame2(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    pub fn clear_frame2(&mut self, spi: &mut SPI) -> Result<(),Error<SPI::Error>> {
         self.use_full_frame(spi)?;
 
         let color=match self.lut_type {
-            LutType::Gray4=>0xff,
-            _=>0,
+            LutType::Gray4=>0,
+            _=>0xff,
         };
 
         self.interface.cmd(spi, cmd::WRITE_BUFFER2_DATA)?;
         self.interface
-            .data_x_times(spi, color, (WIDTH as u32) / 8 * (HEIGHT as u32))?;
+            .data_x_times(spi, color, self.frame_len())?;
         Ok(())
     }
     /// Make both buffers white
-    pub fn clear_frames(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    pub fn clear_frames(&mut self, spi: &mut SPI) -> Result<(),Error<SPI::Error>> {
         self.clear_frame1(spi)?;
         self.clear_frame2(spi)
     }
+    /// Removes ghosting and burn in by refreshing the whole panel black and then white `cycles`
+    /// times with [`LutType::Full`].
+    ///
+    /// Both RAMs are left white. The LUT and the window are restored afterwards, the buffers of
+    /// any [`Display`] are left alone.
+    pub fn deep_clean(&mut self,spi:&mut SPI,cycles:u32)->Result<(),Error<SPI::Error>> {
+        info!("deep clean: {} cycles",cycles);
+        let lut_type=self.lut_type;
+        if lut_type!=LutType::Full {
+            self.load_lut(spi,LutType::Full)?;
+        }
+        for _ in 0..cycles {
+            for color in [0x00,0xff] {
+                self.fill_frames(spi,color)?;
+                self.display_frame(spi)?;
+            }
+        }
+        if lut_type!=LutType::Full {
+            self.load_lut(spi,lut_type)?;
+        }
+        self.use_current_window(spi)
+    }
+    /// Renders a scene band by band instead of from a full framebuffer.
+    ///
+    /// `draw` is called once for every band of rows that fits into `strip` and draws the whole
+    /// scene in `rotation` coordinates; only the pixels inside the band are kept. Each band is
+    /// written to both RAMs right away, which gives the same RAM contents as drawing the scene
+    /// into a [`Display`] and calling [`update_frames`](Self::update_frames). The two strip
+    /// buffers need `(width+7)/8` bytes per row of the panel, e.g. 200 bytes each for 8 rows of
+    /// the SSD1681.
+    ///
+    /// # Panics
+    ///
+    /// If the strip buffers don't hold at least one row.
+    pub fn draw_banded<F:FnMut(&mut Band)>(
+        &mut self,
+        spi:&mut SPI,
+        strip:(&mut [u8],&mut [u8]),
+        rotation:DisplayRotation,
+        mut draw:F,
+    )->Result<(),Error<SPI::Error>> {
+        let panel=self.panel_size();
+        let (width,height)=(panel.width as usize,panel.height as usize);
+        let rows=strip.0.len().min(strip.1.len())/width.div_ceil(8);
+        assert!(rows>0,"strip buffers are smaller than one row");
+        debug!("banded: {} rows per band",rows);
+        let inverted=self.lut_type==LutType::Gray4;
+        let (buffer1,buffer2)=strip;
+        let mut top=0;
+        while top<height {
+            let band_rows=rows.min(height-top);
+            let mut band=Band::new((&mut *buffer1,&mut *buffer2),(width,height),(top,band_rows),rotation,inverted);
+            draw(&mut band);
+            let (bw,red)=band.buffers();
+            let (start,end)=(top as u32,(top+band_rows-1) as u32);
+            let right=self.ram_size().0-1;
+            self.use_ram_area(spi,0,start,right,end)?;
+            self.write_ram(spi,cmd::WRITE_BUFFER1_DATA,bw)?;
+            self.use_ram_area(spi,0,start,right,end)?;
+            self.write_ram(spi,cmd::WRITE_BUFFER2_DATA,red)?;
+            top+=band_rows;
+        }
+        self.use_current_window(spi)
+    }
+
+    /// Writes `color` to every byte of both RAMs
+    fn fill_frames(&mut self,spi:&mut SPI,color:u8)->Result<(),Error<SPI::Error>> {
+        for command in [cmd::WRITE_BUFFER1_DATA,cmd::WRITE_BUFFER2_DATA] {
+            self.use_full_frame(spi)?;
+            self.interface.cmd(spi,command)?;
+            self.interface.data_x_times(spi,color,self.frame_len())?;
+        }
+        Ok(())
+    }
 
-    fn use_full_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    /// Display update sequence for the current LUT
+    fn update_control(&self)->u8 {
+        match (self.lut_type,C::waveform(self.lut_type)) {
+            (LutType::Gray4|LutType::Fast|LutType::Differential,Some(_))=>C::UPDATE_CUSTOM_LUT,
+            _=>C::UPDATE_OTP_LUT,
+        }
+    }
+    /// Display update control for the next refresh, counting fast refreshes and replacing one by
+    /// a full refresh once `full_refresh_interval` were done in a row
+    fn next_update_control(&mut self)->u8 {
+        match self.lut_type {
+            LutType::Fast if self.full_refresh_interval>0&&self.fast_refreshes>=self.full_refresh_interval=>{
+                info!("fast: forcing a full refresh after {} fast ones",self.fast_refreshes);
+                self.fast_refreshes=0;
+                C::UPDATE_OTP_LUT
+            },
+            LutType::Fast=>{
+                self.fast_refreshes+=1;
+                self.update_control()
+            },
+            LutType::Full=>{
+                self.fast_refreshes=0;
+                self.update_control()
+            },
+            _=>self.update_control(),
+        }
+    }
+
+    /// Data entry mode from the configuration with both axes flipped for a 180° rotation
+    fn data_entry(&self)->DataEntryMode {
+        let mode=self.config.data_entry;
+        match self.rotation {
+            DisplayRotation::Rotate180=>DataEntryMode {
+                x_increment:!mode.x_increment,
+                y_increment:!mode.y_increment,
+                ..mode
+            },
+            _=>mode,
+        }
+    }
+
+    /// Width and height of the part of the RAM the panel uses, with the width in whole bytes
+    fn ram_size(&self)->(u32,u32) {
+        let panel=self.panel_size();
+        ((panel.width+7)&!7,panel.height)
+    }
+    /// Number of bytes in one frame of the panel
+    fn frame_len(&self)->u32 {
+        let (width,height)=self.ram_size();
+        width/8*height
+    }
+    /// Fails with [`Error::BufferSize`] unless `buffer` holds one frame of the panel
+    fn check_buffer(&self,buffer:&[u8])->Result<(),Error<SPI::Error>> {
+        let len=self.frame_len() as usize;
+        if buffer.len()!=len {
+            return Err(Error::BufferSize(len));
+        }
+        Ok(())
+    }
+    /// Clips `area` to the panel and widens it to whole bytes
+    fn align(&self,area:&Rectangle)->Rectangle {
+        let panel=self.panel_size();
+        align_to_bytes(area,panel.width,panel.height)
+    }
+
+    /// Writes `buffer` to the RAM selected by `command`, mirroring its bytes if the address
+    /// counter moves left
+    fn write_ram(&mut self,spi:&mut SPI,command:u8,buffer:&[u8])->Result<(),SPI::Error> {
+        self.interface.cmd(spi,command)?;
+        self.write_ram_data(spi,buffer)
+    }
+    /// Writes the part of `buffer` inside `area`, which has to be aligned by `align_to_bytes`, to
+    /// the RAM selected by `command`
+    fn write_ram_area(&mut self,spi:&mut SPI,command:u8,buffer:&[u8],area:Rectangle)->Result<(),Error<SPI::Error>> {
+        let (x,y)=(area.top_left.x as u32,area.top_left.y as u32);
+        let (first,last)=(x/8,(x+area.size.width-1)/8);
+        let (top,bottom)=(y,y+area.size.height-1);
+        let stride=(self.ram_size().0/8) as usize;
+        self.use_ram_area(spi,first*8,top,last*8+7,bottom)?;
+        self.interface.cmd(spi,command)?;
+        for row in top..=bottom {
+            let start=row as usize*stride;
+            self.write_ram_data(spi,&buffer[start+first as usize..=start+last as usize])?;
+        }
+        Ok(())
+    }
+    /// Like `write_ram`, but writes every bit inverted
+    fn write_ram_inverted(&mut self,spi:&mut SPI,command:u8,buffer:&[u8])->Result<(),SPI::Error> {
+        self.interface.cmd(spi,command)?;
+        if self.data_entry().x_increment {
+            self.interface.data_mapped(spi,buffer,|byte|!byte)
+        } else {
+            self.interface.data_mapped(spi,buffer,|byte|!byte.reverse_bits())
+        }
+    }
+    /// Continues a RAM write started by `write_ram`
+    fn write_ram_data(&mut self,spi:&mut SPI,data:&[u8])->Result<(),SPI::Error> {
+        // the leftmost pixel of a byte is its MSB, which has to land on the right when the
+        // counter moves left
+        if self.data_entry().x_increment {
+            self.interface.data(spi,data)
+        } else {
+            self.interface.data_mapped(spi,data,u8::reverse_bits)
+        }
+    }
+
+    fn use_full_frame(&mut self, spi: &mut SPI) -> Result<(),Error<SPI::Error>> {
         // choose full frame/ram
-        self.set_ram_area(spi,0,0,(WIDTH as u32)-1,(HEIGHT as u32)-1)?;
+        let (width,height)=self.ram_size();
+        self.use_ram_area(spi,0,0,width-1,height-1)
+    }
 
-        // start from the beginning
-        self.set_ram_counter(spi,0,0)
+    /// Selects the window set with `set_window`, or the full frame
+    fn use_current_window(&mut self,spi:&mut SPI)->Result<(),Error<SPI::Error>> {
+        match self.window {
+            Some(window)=>self.use_window(spi,window),
+            None=>self.use_full_frame(spi),
+        }
     }
 
-    fn use_window(&mut self,spi:&mut SPI,window:Rectangle)->Result<(),SPI::Error> {
-        let top_left=window.top_left;
-        let bottom_right=top_left+window.size;
-        self.set_ram_area(spi,top_left.x as u32,top_left.y as u32,bottom_right.x as u32,bottom_right.y as u32)?;
-        self.set_ram_counter(spi,top_left.x as u32,top_left.y as u32)
+    /// Selects `window`, which has to be clipped and aligned by `align_to_bytes`
+    fn use_window(&mut self,spi:&mut SPI,window:Rectangle)->Result<(),Error<SPI::Error>> {
+        let (x,y)=(window.top_left.x as u32,window.top_left.y as u32);
+        self.use_ram_area(spi,x,y,x+window.size.width-1,y+window.size.height-1)
+    }
+
+    /// Sets the RAM area and moves the address counter to the corner the data entry mode starts
+    /// from
+    fn use_ram_area(
+        &mut self,
+        spi: &mut SPI,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+    ) -> Result<(),Error<SPI::Error>> {
+        // buffers are mirrored on the axes the counter decrements, so the area is mirrored too
+        let (width,height)=self.ram_size();
+        let mode=self.data_entry();
+        let (start_x,end_x)=if mode.x_increment {(start_x,end_x)} else {(width-1-end_x,width-1-start_x)};
+        let (start_y,end_y)=if mode.y_increment {(start_y,end_y)} else {(height-1-end_y,height-1-start_y)};
+        self.set_ram_area(spi,start_x,start_y,end_x,end_y)?;
+
+        // start from the beginning
+        let x=if mode.x_increment {start_x} else {end_x};
+        let y=if mode.y_increment {start_y} else {end_y};
+        self.set_ram_counter(spi,x,y)
     }
 
     fn set_ram_area(
@@ -312,38 +1041,36 @@ where
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
-        assert!(start_x < end_x);
-        assert!(start_y < end_y);
-
-        self.interface.cmd_with_data(
-            spi,
-            cmd::SET_RAMXPOS,
-            &[(start_x >> 3) as u8,(end_x >> 3) as u8],
-        )?;
-
-        self.interface.cmd_with_data(
-            spi,
-            cmd::SET_RAMYPOS,
-            &[
-                start_y as u8,
-                (start_y >> 8) as u8,
-                end_y as u8,
-                (end_y >> 8) as u8,
-            ],
-        )?;
+    ) -> Result<(),Error<SPI::Error>> {
+        assert!(start_x <= end_x);
+        assert!(start_y <= end_y);
+
+        // the controller counts from start to end, so decrementing axes are given backwards
+        let mode=self.data_entry();
+        let (start_x,end_x)=if mode.x_increment {(start_x,end_x)} else {(end_x,start_x)};
+        let (start_y,end_y)=if mode.y_increment {(start_y,end_y)} else {(end_y,start_y)};
+
+        let mut data=[0;8];
+        encode_address(start_x >> 3,C::RAM_X_BYTES,&mut data[..4]);
+        encode_address(end_x >> 3,C::RAM_X_BYTES,&mut data[C::RAM_X_BYTES..]);
+        self.interface.cmd_with_data(spi,cmd::SET_RAMXPOS,&data[..C::RAM_X_BYTES*2])?;
+
+        encode_address(start_y,C::RAM_Y_BYTES,&mut data[..4]);
+        encode_address(end_y,C::RAM_Y_BYTES,&mut data[C::RAM_Y_BYTES..]);
+        self.interface.cmd_with_data(spi,cmd::SET_RAMYPOS,&data[..C::RAM_Y_BYTES*2])?;
         Ok(())
     }
 
-    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), SPI::Error> {
+    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(),Error<SPI::Error>> {
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
         // aren't relevant
+        let mut data=[0;4];
         self.interface
-            .cmd_with_data(spi, cmd::SET_RAMX_COUNTER, &[(x >> 3) as u8])?;
+            .cmd_with_data(spi, cmd::SET_RAMX_COUNTER, encode_address(x >> 3,C::RAM_X_BYTES,&mut data))?;
 
-        // 2 Databytes: A[7:0] & 0..A[8]
+        // SSD1681: 2 Databytes: A[7:0] & 0..A[8]
         self.interface
-            .cmd_with_data(spi, cmd::SET_RAMY_COUNTER, &[y as u8, (y >> 8) as u8])?;
+            .cmd_with_data(spi, cmd::SET_RAMY_COUNTER, encode_address(y,C::RAM_Y_BYTES,&mut data))?;
         Ok(())
     }
 
@@ -351,7 +1078,7 @@ where
     //     &mut self,
     //     spi: &mut SPI,
     //     delay: &mut DELAY,
-    // ) -> Result<(), SPI::Error> {
+    // ) -> Result<(),Error<SPI::Error>> {
     //     todo!()
     // }
 }
@@ -0,0 +1,531 @@
+//! Software model of the SSD1681 for testing on the host
+//!
+//! Requires the `emulator` feature. An [`Emulator`] hands out an SPI bus, a BUSY pin, a RST pin
+//! and a delay that can be given to [`Ssd1681`](crate::driver::Ssd1681). It decodes the command stream
+//! the same way the controller does: commands are sent with SPI transfers and their parameters
+//! with plain writes. RAM writes follow the RAM window, address counters and data entry mode.
+//!
+//! The panel is a toy model of the electrophoretic film: every pixel has a darkness between 0
+//! (white) and [`SATURATION`] (black) that stays between updates. A display update picks one of
+//! LUT0 to LUT3 for each pixel from its RAM bits (`red << 1 | bw`) and plays the phases of that
+//! LUT: VSH1 and VSH2 darken the pixel by one step per frame, VSL lightens it, VSS leaves it
+//! alone. An update with the LOAD_LUT bit set first loads the OTP waveform, taken to be the
+//! driver's full waveform, into the LUT register, like the controller does.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    convert::Infallible,
+    rc::Rc,
+    vec::Vec,
+};
+use embedded_hal::{
+    delay::blocking::DelayUs,
+    digital::{self, blocking::{InputPin, OutputPin}},
+    spi::{self, blocking::{Operation, Transactional, Write}},
+};
+use crate::{cmd, driver::FULL_WAVEFORM, HEIGHT, WIDTH};
+
+const RAM_SIZE:usize=WIDTH/8*HEIGHT;
+/// Display update control bit that loads the LUT from OTP before refreshing
+const LOAD_LUT:u8=0x10;
+/// Display update control bit that drives the panel
+const DISPLAY:u8=0x04;
+/// Frames of VSH1 that turn a white pixel black
+pub const SATURATION:u8=10;
+/// Length of the SSD1681 LUT without the voltages
+const LUT_LEN:usize=153;
+
+/// A command and the parameters that were sent with it
+#[derive(Clone,Debug,PartialEq)]
+pub struct Command {
+    /// Command byte
+    pub command:u8,
+    /// Parameter bytes
+    pub data:Vec<u8>,
+}
+
+/// The image on the panel after a display update
+#[derive(Clone,Debug,PartialEq)]
+pub struct Panel {
+    bw:Vec<u8>,
+    red:Vec<u8>,
+    update_control:u8,
+    darkness:Vec<u8>,
+    driven:Vec<u32>,
+}
+impl Panel {
+    /// Contents of the black/white RAM (`0x24`) when the update was started
+    pub fn bw_ram(&self)->&[u8] {&self.bw}
+    /// Contents of the red RAM (`0x26`) when the update was started
+    pub fn red_ram(&self)->&[u8] {&self.red}
+    /// The display update control (`0x22`) value that was used
+    pub fn update_control(&self)->u8 {self.update_control}
+    /// Returns `true` if the update used the LUT in the LUT register instead of the one in OTP
+    pub fn custom_lut(&self)->bool {self.update_control&LOAD_LUT==0}
+    /// The LUT (0-3) that drove the pixel at `x`, `y`: `red << 1 | bw` of its RAM bits
+    pub fn lut_index(&self,x:usize,y:usize)->u8 {
+        let idx=x+y*WIDTH;
+        let bit=0b10000000>>(idx%8);
+        (((self.red[idx>>3]&bit!=0) as u8)<<1)|(self.bw[idx>>3]&bit!=0) as u8
+    }
+    /// Darkness of the pixel at `x`, `y` after the update, from 0 (white) to [`SATURATION`]
+    pub fn darkness(&self,x:usize,y:usize)->u8 {self.darkness[x+y*WIDTH]}
+    /// Frames the pixel at `x`, `y` was driven with a voltage other than VSS during the update
+    pub fn driven_frames(&self,x:usize,y:usize)->u32 {self.driven[x+y*WIDTH]}
+    /// Luma (0-3) of the pixel at `x`, `y`, its darkness rounded to 4 levels
+    pub fn luma(&self,x:usize,y:usize)->u8 {
+        let darkness=self.darkness(x,y) as u32*3;
+        3-((darkness+SATURATION as u32/2)/SATURATION as u32) as u8
+    }
+    /// Converts the panel image to a [`Snapshot`](crate::snapshot::Snapshot)
+    #[cfg(feature="snapshot")]
+    pub fn snapshot(&self)->crate::snapshot::Snapshot {
+        let luma=(0..HEIGHT).flat_map(|y|(0..WIDTH).map(move |x|(x,y))).map(|(x,y)|self.luma(x,y)).collect();
+        crate::snapshot::Snapshot::from_luma(WIDTH,HEIGHT,luma)
+    }
+}
+
+/// What one LUT does to a pixel: the darkness after the update for every darkness before it,
+/// and the number of driven frames
+struct Transition {
+    darkness:[u8;SATURATION as usize+1],
+    driven:u32,
+}
+impl Transition {
+    /// Plays LUT `index` of `lut`. A LUT that is too short leaves the pixel alone.
+    fn new(lut:&[u8],index:usize)->Self {
+        let mut transition=Transition {darkness:[0;SATURATION as usize+1],driven:0};
+        for (start,darkness) in transition.darkness.iter_mut().enumerate() {
+            *darkness=start as u8;
+        }
+        if lut.len()<LUT_LEN {
+            return transition;
+        }
+        for group in 0..12 {
+            let vs=lut[index*12+group];
+            let timing=&lut[60+group*7..60+group*7+7];
+            let phases=[(vs>>6,timing[0]),(vs>>4,timing[1]),(vs>>2,timing[3]),(vs,timing[4])];
+            for _ in 0..=timing[6] {
+                for &(level,frames) in phases.iter() {
+                    let step:i32=match level&0b11 {
+                        0b00=>continue,
+                        0b10=>-(frames as i32),
+                        _=>frames as i32,
+                    };
+                    transition.driven+=frames as u32;
+                    for darkness in transition.darkness.iter_mut() {
+                        *darkness=(*darkness as i32+step).clamp(0,SATURATION as i32) as u8;
+                    }
+                }
+            }
+        }
+        transition
+    }
+}
+
+struct State {
+    bw:Vec<u8>,
+    red:Vec<u8>,
+    darkness:Vec<u8>,
+    command:Option<u8>,
+    params:Vec<u8>,
+    registers:BTreeMap<u8,Vec<u8>>,
+    log:Vec<Command>,
+    ram_x:(u8,u8),
+    ram_y:(u16,u16),
+    counter:(u8,u16),
+    data_entry:u8,
+    update_control:u8,
+    panel:Option<Panel>,
+    refreshes:usize,
+    resets:usize,
+    in_reset:bool,
+    busy:u32,
+    busy_polls:u32,
+    sensed_vcom:u8,
+    otp_vcom:u8,
+    otp_display_option:[u8;10],
+    otp_programs:usize,
+    hv_ready:bool,
+    vci_low:bool,
+    chip_id:u8,
+    vci:Option<i32>,
+    transactions:usize,
+    bytes:usize,
+    largest_write:usize,
+    elapsed_us:u64,
+}
+impl State {
+    fn new()->Self {
+        let mut state=State {
+            bw:vec![0;RAM_SIZE],
+            red:vec![0;RAM_SIZE],
+            darkness:vec![0;WIDTH*HEIGHT],
+            command:None,
+            params:Vec::new(),
+            registers:BTreeMap::new(),
+            log:Vec::new(),
+            ram_x:(0,0),
+            ram_y:(0,0),
+            counter:(0,0),
+            data_entry:0,
+            update_control:0,
+            panel:None,
+            refreshes:0,
+            resets:0,
+            in_reset:false,
+            busy:0,
+            busy_polls:1,
+            sensed_vcom:0x20,
+            otp_vcom:0,
+            otp_display_option:[0;10],
+            otp_programs:0,
+            hv_ready:true,
+            vci_low:false,
+            chip_id:0,
+            vci:None,
+            transactions:0,
+            largest_write:0,
+            bytes:0,
+            elapsed_us:0,
+        };
+        state.reset_registers();
+        state
+    }
+    /// Power on values of the registers the emulator models
+    fn reset_registers(&mut self) {
+        self.registers.clear();
+        self.ram_x=(0,(WIDTH/8-1) as u8);
+        self.ram_y=(0,(HEIGHT-1) as u16);
+        self.counter=(0,0);
+        self.data_entry=0b011;
+        self.update_control=0xff;
+    }
+    fn command(&mut self,command:u8) {
+        self.command=Some(command);
+        self.params.clear();
+        self.log.push(Command {command,data:Vec::new()});
+        match command {
+            cmd::SW_RESET=>{
+                self.reset_registers();
+                self.busy=self.busy_polls;
+            },
+            cmd::MASTER_ACTIVATE=>{
+                if self.update_control&LOAD_LUT!=0 {
+                    self.registers.insert(cmd::WRITE_LUT,FULL_WAVEFORM[..LUT_LEN].to_vec());
+                }
+                if self.update_control&DISPLAY!=0 {
+                    self.update_panel();
+                    self.refreshes+=1;
+                }
+                self.busy=self.busy_polls;
+            },
+            cmd::VCOM_SENSE=>{
+                self.registers.insert(cmd::WRITE_VCOM,vec![self.sensed_vcom]);
+                self.busy=self.busy_polls;
+            },
+            cmd::PROGRAM_VCOM_OTP=>{
+                self.otp_vcom=self.registers.get(&cmd::WRITE_VCOM).map_or(0,|vcom|vcom[0]);
+                self.otp_programs+=1;
+                self.busy=self.busy_polls;
+            },
+            cmd::PROGRAM_OTP_SELECTION=>{
+                if let Some(option)=self.registers.get(&cmd::WRITE_DISPLAY_OPTION) {
+                    let len=option.len().min(10);
+                    self.otp_display_option[..len].copy_from_slice(&option[..len]);
+                }
+                self.otp_programs+=1;
+                self.busy=self.busy_polls;
+            },
+            cmd::PROGRAM_WS_OTP=>{
+                self.otp_programs+=1;
+                self.busy=self.busy_polls;
+            },
+            _=>{},
+        }
+    }
+    /// Plays the LUT register on every pixel and captures the result
+    fn update_panel(&mut self) {
+        let lut=self.registers.get(&cmd::WRITE_LUT).map_or(&[][..],|lut|&lut[..]);
+        let transitions=[0,1,2,3].map(|index|Transition::new(lut,index));
+        let mut driven=vec![0;WIDTH*HEIGHT];
+        for (idx,darkness) in self.darkness.iter_mut().enumerate() {
+            let bit=0b10000000>>(idx%8);
+            let index=(((self.red[idx>>3]&bit!=0) as usize)<<1)|(self.bw[idx>>3]&bit!=0) as usize;
+            *darkness=transitions[index].darkness[*darkness as usize];
+            driven[idx]=transitions[index].driven;
+        }
+        self.panel=Some(Panel {
+            bw:self.bw.clone(),
+            red:self.red.clone(),
+            update_control:self.update_control,
+            darkness:self.darkness.clone(),
+            driven,
+        });
+    }
+    /// Answers a read after the last command
+    fn read(&mut self,words:&mut [u8]) {
+        words.fill(0);
+        let answer=match self.command {
+            Some(cmd::READ_DISPLAY_OPTION)=>{
+                let vcom=self.registers.get(&cmd::WRITE_VCOM).map_or(self.otp_vcom,|vcom|vcom[0]);
+                let option=&self.otp_display_option;
+                [&option[..1],&[vcom],&option[1..]].concat()
+            },
+            Some(cmd::READ_USER_ID)=>self.registers.get(&cmd::WRITE_USER_ID).cloned().unwrap_or_default(),
+            Some(cmd::READ_STATUS)=>vec![
+                (!self.hv_ready as u8)<<5|(self.vci_low as u8)<<4|((self.busy>0) as u8)<<2|self.chip_id&0b11,
+            ],
+            _=>return,
+        };
+        let len=words.len().min(answer.len());
+        words[..len].copy_from_slice(&answer[..len]);
+    }
+    fn data(&mut self,data:&[u8]) {
+        let command=match self.command {
+            Some(command)=>command,
+            None=>return,
+        };
+        if let Some(last)=self.log.last_mut() {
+            last.data.extend_from_slice(data);
+        }
+        for &byte in data {
+            match command {
+                cmd::WRITE_BUFFER1_DATA|cmd::WRITE_BUFFER2_DATA=>self.write_ram(command,byte),
+                _=>self.params.push(byte),
+            }
+        }
+        let p=&self.params;
+        match command {
+            cmd::SET_RAMXPOS if p.len()>=2=>self.ram_x=(p[0]&0x3f,p[1]&0x3f),
+            cmd::SET_RAMYPOS if p.len()>=4=>self.ram_y=(u16::from_le_bytes([p[0],p[1]&1]),u16::from_le_bytes([p[2],p[3]&1])),
+            cmd::SET_RAMX_COUNTER if !p.is_empty()=>self.counter.0=p[0]&0x3f,
+            cmd::SET_RAMY_COUNTER if p.len()>=2=>self.counter.1=u16::from_le_bytes([p[0],p[1]&1]),
+            cmd::DATA_ENTRY_MODE if !p.is_empty()=>self.data_entry=p[0]&0b111,
+            cmd::UPDATE_DISPLAY_CTRL2 if !p.is_empty()=>self.update_control=p[0],
+            cmd::HV_READY_DETECTION|cmd::VCI_DETECTION if p.len()==1=>{
+                if let (cmd::VCI_DETECTION,Some(vci))=(command,self.vci) {
+                    self.vci_low=vci<2_200+((p[0]&0b111) as i32-3)*100;
+                }
+                self.busy=self.busy_polls;
+            },
+            _=>{},
+        }
+        if !self.params.is_empty() {
+            self.registers.insert(command,self.params.clone());
+        }
+    }
+    fn write_ram(&mut self,command:u8,byte:u8) {
+        let (x,y)=(self.counter.0 as usize,self.counter.1 as usize);
+        if x<WIDTH/8&&y<HEIGHT {
+            let ram=if command==cmd::WRITE_BUFFER1_DATA {&mut self.bw} else {&mut self.red};
+            ram[x+y*(WIDTH/8)]=byte;
+        }
+        self.advance_counter();
+    }
+    /// Moves the address counter like the controller does after a RAM write
+    fn advance_counter(&mut self) {
+        let x_inc=self.data_entry&0b001!=0;
+        let y_inc=self.data_entry&0b010!=0;
+        let y_first=self.data_entry&0b100!=0;
+        let step_x=|x:u8|if x_inc {x.wrapping_add(1)} else {x.wrapping_sub(1)};
+        let step_y=|y:u16|if y_inc {y.wrapping_add(1)} else {y.wrapping_sub(1)};
+        let (x,y)=self.counter;
+        self.counter=if y_first {
+            if y==self.ram_y.1 {
+                let x=if x==self.ram_x.1 {self.ram_x.0} else {step_x(x)};
+                (x,self.ram_y.0)
+            } else {
+                (x,step_y(y))
+            }
+        } else if x==self.ram_x.1 {
+            let y=if y==self.ram_y.1 {self.ram_y.0} else {step_y(y)};
+            (self.ram_x.0,y)
+        } else {
+            (step_x(x),y)
+        };
+    }
+}
+
+/// Emulated SSD1681 controller and panel.
+///
+/// Cloning an emulator gives another handle to the same controller.
+#[derive(Clone)]
+pub struct Emulator {
+    state:Rc<RefCell<State>>,
+}
+impl Default for Emulator {
+    fn default()->Self {Self::new()}
+}
+impl Emulator {
+    /// Creates a powered up controller with empty RAM
+    pub fn new()->Self {
+        Emulator {state:Rc::new(RefCell::new(State::new()))}
+    }
+    /// Sets the VCOM register value VCOM sensing measures
+    pub fn set_sensed_vcom(&self,vcom:u8) {self.state.borrow_mut().sensed_vcom=vcom}
+    /// VCOM programmed into OTP
+    pub fn otp_vcom(&self)->u8 {self.state.borrow().otp_vcom}
+    /// Display option programmed into OTP, in `WRITE_DISPLAY_OPTION` order
+    pub fn otp_display_option(&self)->[u8;10] {self.state.borrow().otp_display_option}
+    /// Number of OTP programming commands
+    pub fn otp_programs(&self)->usize {self.state.borrow().otp_programs}
+    /// Sets the status bits reported by `READ_STATUS`
+    pub fn set_status(&self,hv_ready:bool,vci_low:bool,chip_id:u8) {
+        let mut state=self.state.borrow_mut();
+        state.hv_ready=hv_ready;
+        state.vci_low=vci_low;
+        state.chip_id=chip_id;
+    }
+    /// Number of SPI transactions (writes and `exec` calls) since the emulator was created
+    pub fn transactions(&self)->usize {self.state.borrow().transactions}
+    /// Number of data bytes written over SPI since the emulator was created
+    pub fn bytes_written(&self)->usize {self.state.borrow().bytes}
+    /// Most data bytes sent in a single SPI write since the commands were last cleared
+    pub fn largest_write(&self)->usize {self.state.borrow().largest_write}
+    /// Sets the supply voltage VCI detection compares against, in millivolts
+    pub fn set_vci(&self,millivolts:i32) {self.state.borrow_mut().vci=Some(millivolts)}
+    /// Returns the SPI bus connected to the controller
+    pub fn spi(&self)->Spi {Spi {state:self.state.clone()}}
+    /// Returns the BUSY pin of the controller
+    pub fn busy(&self)->Busy {Busy {state:self.state.clone()}}
+    /// Returns the RST pin of the controller
+    pub fn rst(&self)->Reset {Reset {state:self.state.clone()}}
+    /// Returns a delay that counts the time it was asked to wait
+    pub fn delay(&self)->Delay {Delay {state:self.state.clone()}}
+    /// Time in µs all delays of this emulator waited for
+    pub fn elapsed_us(&self)->u64 {self.state.borrow().elapsed_us}
+
+    /// Sets how many times BUSY reads high after a reset or a display update
+    pub fn set_busy_polls(&self,polls:u32) {self.state.borrow_mut().busy_polls=polls}
+    /// Contents of the black/white RAM (`0x24`)
+    pub fn bw_ram(&self)->Vec<u8> {self.state.borrow().bw.clone()}
+    /// Contents of the red RAM (`0x26`)
+    pub fn red_ram(&self)->Vec<u8> {self.state.borrow().red.clone()}
+    /// Image on the panel after the last display update, if there was one
+    pub fn panel(&self)->Option<Panel> {self.state.borrow().panel.clone()}
+    /// Number of display updates that drove the panel
+    pub fn refreshes(&self)->usize {self.state.borrow().refreshes}
+    /// Number of hardware resets
+    pub fn resets(&self)->usize {self.state.borrow().resets}
+    /// Every command received so far
+    pub fn commands(&self)->Vec<Command> {self.state.borrow().log.clone()}
+    /// Forgets the commands received so far and the [largest write](Self::largest_write)
+    pub fn clear_commands(&self) {
+        let mut state=self.state.borrow_mut();
+        state.log.clear();
+        state.largest_write=0;
+    }
+    /// Last parameters written with `command` since the last reset
+    pub fn register(&self,command:u8)->Option<Vec<u8>> {
+        self.state.borrow().registers.get(&command).cloned()
+    }
+    /// RAM window as `((x_start, x_end), (y_start, y_end))`, X being in bytes
+    pub fn ram_window(&self)->((u8,u8),(u16,u16)) {
+        let state=self.state.borrow();
+        (state.ram_x,state.ram_y)
+    }
+    /// RAM address counter as `(x, y)`, X being in bytes
+    pub fn ram_counter(&self)->(u8,u16) {self.state.borrow().counter}
+    /// Data entry mode (`0x11`)
+    pub fn data_entry_mode(&self)->u8 {self.state.borrow().data_entry}
+}
+
+/// SPI bus of an [`Emulator`]
+pub struct Spi {
+    state:Rc<RefCell<State>>,
+}
+impl spi::ErrorType for Spi {
+    type Error=Infallible;
+}
+impl Write for Spi {
+    fn write(&mut self,words:&[u8])->Result<(),Self::Error> {
+        let mut state=self.state.borrow_mut();
+        state.transactions+=1;
+        state.bytes+=words.len();
+        state.largest_write=state.largest_write.max(words.len());
+        state.data(words);
+        Ok(())
+    }
+}
+impl Transactional for Spi {
+    fn exec<'a>(&mut self,operations:&mut [Operation<'a,u8>])->Result<(),Self::Error> {
+        let mut state=self.state.borrow_mut();
+        state.transactions+=1;
+        for operation in operations {
+            match operation {
+                Operation::Transfer(read,write)=>{
+                    write.iter().for_each(|&command|state.command(command));
+                    read.fill(0);
+                },
+                Operation::TransferInPlace(words)=>{
+                    words.iter().for_each(|&command|state.command(command));
+                    words.fill(0);
+                },
+                Operation::Write(words)=>{
+                    state.bytes+=words.len();
+                    state.largest_write=state.largest_write.max(words.len());
+                    state.data(words);
+                },
+                Operation::Read(words)=>state.read(words),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// BUSY pin of an [`Emulator`]
+pub struct Busy {
+    state:Rc<RefCell<State>>,
+}
+impl digital::ErrorType for Busy {
+    type Error=Infallible;
+}
+impl InputPin for Busy {
+    fn is_high(&self)->Result<bool,Self::Error> {
+        let mut state=self.state.borrow_mut();
+        let busy=state.busy>0;
+        state.busy=state.busy.saturating_sub(1);
+        Ok(busy)
+    }
+    fn is_low(&self)->Result<bool,Self::Error> {
+        self.is_high().map(|busy|!busy)
+    }
+}
+
+/// RST pin of an [`Emulator`]
+pub struct Reset {
+    state:Rc<RefCell<State>>,
+}
+impl digital::ErrorType for Reset {
+    type Error=Infallible;
+}
+impl OutputPin for Reset {
+    fn set_low(&mut self)->Result<(),Self::Error> {
+        let mut state=self.state.borrow_mut();
+        state.in_reset=true;
+        state.reset_registers();
+        Ok(())
+    }
+    fn set_high(&mut self)->Result<(),Self::Error> {
+        let mut state=self.state.borrow_mut();
+        if state.in_reset {
+            state.in_reset=false;
+            state.resets+=1;
+        }
+        Ok(())
+    }
+}
+
+/// Delay of an [`Emulator`]. Returns immediately and adds the time to [`Emulator::elapsed_us`].
+pub struct Delay {
+    state:Rc<RefCell<State>>,
+}
+impl DelayUs for Delay {
+    type Error=Infallible;
+    fn delay_us(&mut self,us:u32)->Result<(),Self::Error> {
+        self.state.borrow_mut().elapsed_us+=us as u64;
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! Logging macros that forward to `log` or `defmt` when their feature is enabled, and compile to
+//! nothing otherwise.
+//!
+//! Without either feature the arguments only appear in a branch that never runs, so they are
+//! type checked and count as used but are never evaluated.
+#![allow(unused_macros)]
+
+macro_rules! trace {
+    ($s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature="log")]
+        ::log::trace!($s $(, $x)*);
+        #[cfg(feature="defmt")]
+        ::defmt::trace!($s $(, $x)*);
+        #[cfg(not(any(feature="log",feature="defmt")))]
+        if false {
+            let _=($(&$x),*);
+        }
+    }};
+}
+
+macro_rules! debug {
+    ($s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature="log")]
+        ::log::debug!($s $(, $x)*);
+        #[cfg(feature="defmt")]
+        ::defmt::debug!($s $(, $x)*);
+        #[cfg(not(any(feature="log",feature="defmt")))]
+        if false {
+            let _=($(&$x),*);
+        }
+    }};
+}
+
+macro_rules! info {
+    ($s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature="log")]
+        ::log::info!($s $(, $x)*);
+        #[cfg(feature="defmt")]
+        ::defmt::info!($s $(, $x)*);
+        #[cfg(not(any(feature="log",feature="defmt")))]
+        if false {
+            let _=($(&$x),*);
+        }
+    }};
+}
+
+macro_rules! warn {
+    ($s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature="log")]
+        ::log::warn!($s $(, $x)*);
+        #[cfg(feature="defmt")]
+        ::defmt::warn!($s $(, $x)*);
+        #[cfg(not(any(feature="log",feature="defmt")))]
+        if false {
+            let _=($(&$x),*);
+        }
+    }};
+}
@@ -1,6 +1,7 @@
 //! Graphics Support for EPDs
 /// TODO: Tests (maybe)
 
+#[cfg(feature="alloc")]
 use alloc::vec::Vec;
 use crate::color::{
     Color,
@@ -19,7 +20,8 @@ use embedded_graphics_core::{
 };
 
 /// Displayrotation
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
 pub enum DisplayRotation {
     /// No rotation
     Rotate0,
@@ -35,6 +37,23 @@ impl Default for DisplayRotation {
         DisplayRotation::Rotate0
     }
 }
+impl DisplayRotation {
+    /// Maps `area` from rotated coordinates to the unrotated coordinates of a `panel` sized
+    /// panel, which the driver's windows and RAM areas use. Parts outside of the panel stay
+    /// outside.
+    pub fn to_panel(&self,area:&Rectangle,panel:Size)->Rectangle {
+        let (width,height)=(panel.width as i32,panel.height as i32);
+        let Point {x,y}=area.top_left;
+        let Size {width:w,height:h}=area.size;
+        let (top_left,size)=match self {
+            DisplayRotation::Rotate0=>(Point::new(x,y),Size::new(w,h)),
+            DisplayRotation::Rotate90=>(Point::new(width-y-h as i32,x),Size::new(h,w)),
+            DisplayRotation::Rotate180=>(Point::new(width-x-w as i32,height-y-h as i32),Size::new(w,h)),
+            DisplayRotation::Rotate270=>(Point::new(y,height-x-w as i32),Size::new(h,w)),
+        };
+        Rectangle::new(top_left,size)
+    }
+}
 
 /// Necessary traits for all displays to implement for drawing
 ///
@@ -55,14 +74,347 @@ pub trait Display:DrawTarget {
     fn rotation(&self) -> DisplayRotation;
     /// Inverts the display for B/W mode. The gray4 mode has black and white swapped (for now).
     fn invert_display(&mut self);
+    /// Returns `true` if the buffers are currently inverted.
+    ///
+    /// Defaults to `false`; displays whose `invert_display` inverts the buffers have to track it.
+    fn is_inverted(&self)->bool {false}
+    /// Size of the unrotated panel the buffers are laid out for, 200x200 by default
+    fn panel_size(&self)->Size {Size::new(WIDTH as u32,HEIGHT as u32)}
+    /// Copies a [`PackedImage`] into the buffers with its top left corner at `position`.
+    ///
+    /// `position` is in rotated coordinates, just like the points given to `draw_iter`. The
+    /// default implementation draws the visible pixels one by one with `draw_iter`.
+    fn blit(&mut self,image:&PackedImage,position:Point) where Self:Sized+DrawTarget<Color=Color> {
+        let Size {width,height}=image.size();
+        let pixels=(0..height).flat_map(|y|(0..width).map(move |x|(x,y)))
+            .filter_map(|(x,y)|Some(Pixel(position+Point::new(x as i32,y as i32),Color::new(image.luma(x,y)?))));
+        self.draw_iter(pixels).ok();
+    }
+}
+
+/// Pixel format of a [`PackedImage`]
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum PackedFormat {
+    /// 1 bit per pixel, MSB first. Set bits are white, cleared bits are black.
+    Bpp1,
+    /// 2 bits per pixel, MSB first. Each pair of bits is the luma of a [`Color`].
+    Bpp2,
+}
+impl PackedFormat {
+    /// Number of bits used for one pixel
+    pub fn bits_per_pixel(&self)->usize {
+        match self {
+            PackedFormat::Bpp1=>1,
+            PackedFormat::Bpp2=>2,
+        }
+    }
+}
+
+/// A pre-converted bitmap that can be copied into a [`Display`] with [`Display::blit`].
+///
+/// Rows start on a byte boundary and are `stride` bytes apart. The optional mask is a 1bpp
+/// bitmap of the same size where set bits are drawn and cleared bits are left transparent.
+#[derive(Copy,Clone,Debug)]
+pub struct PackedImage<'a> {
+    data:&'a [u8],
+    format:PackedFormat,
+    size:Size,
+    stride:usize,
+    mask:Option<(&'a [u8],usize)>,
+}
+impl<'a> PackedImage<'a> {
+    /// Creates an image from tightly packed rows
+    pub fn new(data:&'a [u8],format:PackedFormat,size:Size)->Self {
+        let stride=(size.width as usize*format.bits_per_pixel()).div_ceil(8);
+        Self::with_stride(data,format,size,stride)
+    }
+    /// Creates an image whose rows are `stride` bytes apart
+    pub fn with_stride(data:&'a [u8],format:PackedFormat,size:Size,stride:usize)->Self {
+        assert!(stride*8>=size.width as usize*format.bits_per_pixel());
+        assert!(data.len()>=stride*size.height as usize);
+        PackedImage {data,format,size,stride,mask:None}
+    }
+    /// Adds a tightly packed transparency mask
+    pub fn mask(self,mask:&'a [u8])->Self {
+        let stride=(self.size.width as usize).div_ceil(8);
+        self.mask_with_stride(mask,stride)
+    }
+    /// Adds a transparency mask whose rows are `stride` bytes apart
+    pub fn mask_with_stride(mut self,mask:&'a [u8],stride:usize)->Self {
+        assert!(stride*8>=self.size.width as usize);
+        assert!(mask.len()>=stride*self.size.height as usize);
+        self.mask=Some((mask,stride));
+        self
+    }
+    /// Size of the image in pixels
+    pub fn size(&self)->Size {self.size}
+    /// Format of the image data
+    pub fn format(&self)->PackedFormat {self.format}
+    /// Returns the luma of a pixel, or `None` if it is outside the image or masked out
+    pub fn luma(&self,x:u32,y:u32)->Option<u8> {
+        if x>=self.size.width||y>=self.size.height {
+            return None;
+        }
+        let (lo,hi,valid)=self.row_bits(y as usize,x as isize);
+        if valid&0x80==0 {
+            return None;
+        }
+        Some((((hi>>7)&1)<<1)|((lo>>7)&1))
+    }
+    /// Reads 8 pixels of row `y` starting at `x`, MSB first.
+    ///
+    /// Returns the low luma bits, the high luma bits and a mask of the pixels that should be
+    /// drawn.
+    fn row_bits(&self,y:usize,x:isize)->(u8,u8,u8) {
+        let row=&self.data[y*self.stride..(y+1)*self.stride];
+        let (lo,hi)=match self.format {
+            PackedFormat::Bpp1=>{
+                let bits=read_bits(row,x);
+                (bits,bits)
+            },
+            PackedFormat::Bpp2=>{
+                let pairs=((read_bits(row,x*2) as u16)<<8)|read_bits(row,x*2+8) as u16;
+                let mut lo=0;
+                let mut hi=0;
+                for i in 0..8 {
+                    let pair=(pairs>>(14-i*2))&0b11;
+                    lo|=((pair&1) as u8)<<(7-i);
+                    hi|=((pair>>1) as u8)<<(7-i);
+                }
+                (lo,hi)
+            },
+        };
+        let mut valid=0xff;
+        if x<0 {
+            valid=(valid as u32).checked_shr(-x as u32).unwrap_or(0) as u8;
+        }
+        let remaining=self.size.width as isize-x;
+        if remaining<8 {
+            valid&=!(0xff>>remaining.max(0));
+        }
+        if let Some((mask,stride))=self.mask {
+            valid&=read_bits(&mask[y*stride..(y+1)*stride],x);
+        }
+        (lo,hi,valid)
+    }
+}
+
+/// Reads 8 bits starting at bit offset `bit` of `row`. Bits outside of `row` read as 0.
+fn read_bits(row:&[u8],bit:isize)->u8 {
+    let byte=bit.div_euclid(8);
+    let shift=bit.rem_euclid(8);
+    let get=|i:isize|if i>=0&&(i as usize)<row.len() {row[i as usize]} else {0};
+    let bits=((get(byte) as u16)<<8)|get(byte+1) as u16;
+    ((bits<<shift)>>8) as u8
 }
 
+/// Maps a point in rotated coordinates to the unrotated coordinates of a `width`x`height` panel
+pub(crate) fn rotate_point(rotation:DisplayRotation,(width,height):(usize,usize),x:usize,y:usize)->(usize,usize) {
+    match rotation {
+        DisplayRotation::Rotate0=>(x,y),
+        DisplayRotation::Rotate90=>(width-1-y,x),
+        DisplayRotation::Rotate180=>(width-1-x,height-1-y),
+        DisplayRotation::Rotate270=>(y,height-1-x),
+    }
+}
+/// Size of a `width`x`height` panel in rotated coordinates
+pub(crate) fn rotated_size(rotation:DisplayRotation,(width,height):(usize,usize))->(usize,usize) {
+    match rotation {
+        DisplayRotation::Rotate0|DisplayRotation::Rotate180=>(width,height),
+        DisplayRotation::Rotate90|DisplayRotation::Rotate270=>(height,width),
+    }
+}
+
+/// A strip of whole panel rows for drawing without a full framebuffer.
+///
+/// Handed to the draw closure of [`Ssd1681::draw_banded`](crate::driver::Ssd1681::draw_banded)
+/// once per band. It accepts the points of the whole (rotated) screen and keeps the pixels that
+/// fall into the band, so the closure can draw the complete scene every time.
+/// [`Band::area`] tells which part of the screen is kept, to skip drawing outside of it.
+pub struct Band<'a> {
+    buffer:(&'a mut [u8],&'a mut [u8]),
+    width:usize,
+    height:usize,
+    top:usize,
+    rows:usize,
+    rotation:DisplayRotation,
+    inverted:bool,
+}
+impl<'a> Band<'a> {
+    /// A band of `rows` rows starting at panel row `top` of a `width`x`height` panel. The
+    /// buffers start out like those of [`Display1in54::new`].
+    pub(crate) fn new(
+        buffer:(&'a mut [u8],&'a mut [u8]),
+        (width,height):(usize,usize),
+        (top,rows):(usize,usize),
+        rotation:DisplayRotation,
+        inverted:bool,
+    )->Self {
+        let len=width.div_ceil(8)*rows;
+        buffer.0[..len].fill(0xff);
+        buffer.1[..len].fill(0xff);
+        Band {buffer,width,height,top,rows,rotation,inverted}
+    }
+    /// The part of the rotated screen this band covers
+    pub fn area(&self)->Rectangle {
+        let (width,height,top,rows)=(self.width as u32,self.height as u32,self.top as u32,self.rows as u32);
+        match self.rotation {
+            DisplayRotation::Rotate0=>Rectangle::new(Point::new(0,top as i32),Size::new(width,rows)),
+            DisplayRotation::Rotate90=>Rectangle::new(Point::new(top as i32,0),Size::new(rows,width)),
+            DisplayRotation::Rotate180=>Rectangle::new(Point::new(0,(height-top-rows) as i32),Size::new(width,rows)),
+            DisplayRotation::Rotate270=>Rectangle::new(Point::new((height-top-rows) as i32,0),Size::new(rows,width)),
+        }
+    }
+    /// The bytes of the band for buffer1 and buffer2
+    pub(crate) fn buffers(&self)->(&[u8],&[u8]) {
+        let len=self.width.div_ceil(8)*self.rows;
+        (&self.buffer.0[..len],&self.buffer.1[..len])
+    }
+    /// Maps a rotated point to the panel, returns `None` outside of the band
+    fn panel_point(&self,point:Point)->Option<(usize,usize)> {
+        let panel=(self.width,self.height);
+        let (width,height)=rotated_size(self.rotation,panel);
+        if point.x<0||point.y<0||point.x as usize>=width||point.y as usize>=height {
+            return None;
+        }
+        let (x,y)=rotate_point(self.rotation,panel,point.x as usize,point.y as usize);
+        if y<self.top||y>=self.top+self.rows {
+            return None;
+        }
+        Some((x,y-self.top))
+    }
+}
+impl DrawTarget for Band<'_> {
+    type Color=Color;
+    type Error=core::convert::Infallible;
+    fn draw_iter<I:IntoIterator<Item=Pixel<Color>>>(&mut self,pixels:I)->Result<(),Self::Error> {
+        for Pixel(point,color) in pixels {
+            if let Some((x,y))=self.panel_point(point) {
+                let idx=y*self.width.div_ceil(8)+x/8;
+                let bit=0b10000000>>(x%8);
+                let luma=color.luma();
+                for (plane,set) in [(&mut *self.buffer.0,luma&1==1),(&mut *self.buffer.1,luma&2==2)] {
+                    if set!=self.inverted {
+                        plane[idx]|=bit;
+                    } else {
+                        plane[idx]&=!bit;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Dimensions for Band<'_> {
+    fn bounding_box(&self)->Rectangle {
+        let (width,height)=rotated_size(self.rotation,(self.width,self.height));
+        Rectangle::new(Point::zero(),Size::new(width as u32,height as u32))
+    }
+}
+
+/// Reads the luma of an unrotated pixel back out of the two bit planes
+#[cfg(feature="snapshot")]
+pub(crate) fn plane_luma(planes:(&[u8],&[u8]),inverted:bool,x:usize,y:usize)->u8 {
+    let idx=x+y*WIDTH;
+    let bit=0b10000000>>(idx%8);
+    let lo=(planes.0[idx>>3]&bit!=0)!=inverted;
+    let hi=(planes.1[idx>>3]&bit!=0)!=inverted;
+    ((hi as u8)<<1)|lo as u8
+}
+
+/// Copies `image` into the two bit planes of a [`WIDTH`]x[`HEIGHT`] buffer.
+///
+/// Unrotated and upside down buffers are written a byte at a time, other rotations fall back to
+/// writing single pixels.
+pub(crate) fn blit_planes(
+    planes:(&mut [u8],&mut [u8]),
+    rotation:DisplayRotation,
+    inverted:bool,
+    image:&PackedImage,
+    position:Point,
+) {
+    let (width,height)=rotated_size(rotation,(WIDTH,HEIGHT));
+    let x0=position.x.max(0) as usize;
+    let y0=position.y.max(0) as usize;
+    let x1=(position.x+image.size.width as i32).clamp(0,width as i32) as usize;
+    let y1=(position.y+image.size.height as i32).clamp(0,height as i32) as usize;
+    if x0>=x1||y0>=y1 {
+        return;
+    }
+    // Inverted buffers store a cleared bit for a set color bit
+    let flip=if inverted {0xff} else {0};
+    match rotation {
+        DisplayRotation::Rotate0|DisplayRotation::Rotate180=>{
+            let upside_down=rotation==DisplayRotation::Rotate180;
+            let (first,last)=if upside_down {
+                ((WIDTH-x1)/8,(WIDTH-x0-1)/8)
+            } else {
+                (x0/8,(x1-1)/8)
+            };
+            for y in y0..y1 {
+                let src_y=(y as i32-position.y) as usize;
+                let row=if upside_down {HEIGHT-1-y} else {y};
+                for column in first..=last {
+                    // first rotated x coordinate covered by this byte
+                    let x=if upside_down {WIDTH-8-column*8} else {column*8};
+                    let (mut lo,mut hi,mut valid)=image.row_bits(src_y,x as isize-position.x as isize);
+                    if upside_down {
+                        lo=lo.reverse_bits();
+                        hi=hi.reverse_bits();
+                        valid=valid.reverse_bits();
+                    }
+                    let idx=row*(WIDTH/8)+column;
+                    planes.0[idx]=(planes.0[idx]&!valid)|((lo^flip)&valid);
+                    planes.1[idx]=(planes.1[idx]&!valid)|((hi^flip)&valid);
+                }
+            }
+        },
+        DisplayRotation::Rotate90|DisplayRotation::Rotate270=>{
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let src_x=(x as i32-position.x) as u32;
+                    let src_y=(y as i32-position.y) as u32;
+                    if let Some(luma)=image.luma(src_x,src_y) {
+                        let (px,py)=rotate_point(rotation,(WIDTH,HEIGHT),x,y);
+                        let idx=px+py*WIDTH;
+                        let bit=0b10000000>>(idx%8);
+                        let lo=(luma&1==1)!=inverted;
+                        let hi=(luma&2==2)!=inverted;
+                        if lo {
+                            planes.0[idx>>3]|=bit;
+                        } else {
+                            planes.0[idx>>3]&=!bit;
+                        }
+                        if hi {
+                            planes.1[idx>>3]|=bit;
+                        } else {
+                            planes.1[idx>>3]&=!bit;
+                        }
+                    }
+                }
+            }
+        },
+    }
+}
+
+/// Length of each buffer of a [`Display1in54`] in bytes
+pub const BUFFER_LEN:usize=(WIDTH*HEIGHT)/8;
+
+/// Buffer storage of [`Display1in54::new`]: a `Vec` with the `alloc` feature, an inline array
+/// without it
+#[cfg(feature="alloc")]
+pub type DefaultBuffer=Vec<u8>;
+/// Buffer storage of [`Display1in54::new`]: a `Vec` with the `alloc` feature, an inline array
+/// without it
+#[cfg(not(feature="alloc"))]
+pub type DefaultBuffer=[u8;BUFFER_LEN];
+
 /// Display for a 200x200 panel
-pub struct Display1in54 {
-    #[cfg(not(feature="alloc"))]
-    buffer:([u8;(WIDTH*HEIGHT)/8],[u8;(WIDTH*HEIGHT)/8]),
-    #[cfg(feature="alloc")]
-    buffer:(Vec<u8>,Vec<u8>),
+///
+/// `B` is the storage of the two buffers. [`Display1in54::new`] owns them,
+/// [`Display1in54::with_buffers`] borrows them from the caller.
+pub struct Display1in54<B=DefaultBuffer> {
+    buffer:(B,B),
     rotation: DisplayRotation,
     inverted:bool,
 }
@@ -71,13 +423,35 @@ impl Display1in54 {
     pub fn new()->Self {
         Display1in54 {
             #[cfg(not(feature="alloc"))]
-            buffer:([0xff;(WIDTH*HEIGHT)/8],[0xff;(WIDTH*HEIGHT)/8]),
+            buffer:([0xff;BUFFER_LEN],[0xff;BUFFER_LEN]),
             #[cfg(feature="alloc")]
-            buffer:(vec![0xff;(WIDTH*HEIGHT)/8],vec![0xff;(WIDTH*HEIGHT)/8]),
+            buffer:(vec![0xff;BUFFER_LEN],vec![0xff;BUFFER_LEN]),
             rotation:DisplayRotation::default(),
             inverted:false,
         }
     }
+}
+impl<'a> Display1in54<&'a mut [u8;BUFFER_LEN]> {
+    /// Create a display in buffers provided by the caller, without heap or stack usage.
+    ///
+    /// With `&'static mut` buffers, e.g. from `static_cell` or `cortex_m::singleton!`, the
+    /// buffers live in .bss and the display itself is only a few bytes. The buffers are reset
+    /// like those of [`Display1in54::new`].
+    pub fn with_buffers(buffer1:&'a mut [u8;BUFFER_LEN],buffer2:&'a mut [u8;BUFFER_LEN])->Self {
+        buffer1.fill(0xff);
+        buffer2.fill(0xff);
+        Display1in54 {
+            buffer:(buffer1,buffer2),
+            rotation:DisplayRotation::default(),
+            inverted:false,
+        }
+    }
+}
+impl<B:AsRef<[u8]>+AsMut<[u8]>> Display1in54<B> {
+    /// Both buffers, mutably
+    fn planes(&mut self)->(&mut [u8],&mut [u8]) {
+        (self.buffer.0.as_mut(),self.buffer.1.as_mut())
+    }
     fn get_color_bits(&self,color:Color)->(bool,bool) {
         let luma=color.luma();
         let color1=(luma&1)==1;
@@ -89,50 +463,29 @@ impl Display1in54 {
         }
     }
 }
-impl DrawTarget for Display1in54 {
+impl<B:AsRef<[u8]>+AsMut<[u8]>> DrawTarget for Display1in54<B> {
     type Color=Color;
     type Error=core::convert::Infallible;
     fn draw_iter<I:IntoIterator<Item=Pixel<Color>>>(&mut self,pixels:I)->Result<(),Self::Error> {
-        use DisplayRotation::*;
-        for pixel in pixels {
-            let pos=pixel.0;
-            if pos.x<(WIDTH as i32)&&pos.y<(HEIGHT as i32)&&pos.x>0&&pos.y>0 {
-                let x=pos.x as usize;
-                let y=pos.y as usize;
-                let color=self.get_color_bits(pixel.1);
-                match self.rotation {
-                    Rotate0=>{
-                        let mut idx=x+(y*WIDTH);
-                        let bit=0b10000000>>(idx%8);
-                        idx>>=3;
-                        if color.0 {
-                            self.buffer.0[idx]&=!bit;
-                        } else {
-                            self.buffer.0[idx]|=bit;
-                        }
-                        if color.1 {
-                            self.buffer.1[idx]&=!bit;
-                        } else {
-                            self.buffer.1[idx]|=bit;
-                        }
-                    },
-                    Rotate180=>{
-                        let mut idx=((WIDTH-1)-x)+(((HEIGHT-1)-y)*WIDTH);
-                        let bit=0b10000000>>(idx%8);
-                        idx>>=3;
-                        if color.0 {
-                            self.buffer.0[idx]&=!bit;
-                        } else {
-                            self.buffer.0[idx]|=bit;
-                        }
-                        if color.1 {
-                            self.buffer.1[idx]&=!bit;
-                        } else {
-                            self.buffer.1[idx]|=bit;
-                        }
-                    },
-                    _=>todo!(),
-                }
+        let (width,height)=rotated_size(self.rotation,(WIDTH,HEIGHT));
+        for Pixel(point,color) in pixels {
+            if point.x<0||point.y<0||point.x>=width as i32||point.y>=height as i32 {
+                continue;
+            }
+            let (x,y)=rotate_point(self.rotation,(WIDTH,HEIGHT),point.x as usize,point.y as usize);
+            let color=self.get_color_bits(color);
+            let buffer=self.planes();
+            let idx=(x+y*WIDTH)>>3;
+            let bit=0b10000000>>(x%8);
+            if color.0 {
+                buffer.0[idx]|=bit;
+            } else {
+                buffer.0[idx]&=!bit;
+            }
+            if color.1 {
+                buffer.1[idx]|=bit;
+            } else {
+                buffer.1[idx]&=!bit;
             }
         }
         return Ok(());
@@ -142,41 +495,46 @@ impl DrawTarget for Display1in54 {
         return Ok(());
     }
 }
-impl Dimensions for Display1in54 {
+impl<B> Dimensions for Display1in54<B> {
     fn bounding_box(&self)->Rectangle {
         Rectangle::new(Point::zero(),Size::new(WIDTH as u32,HEIGHT as u32))
     }
 }
 
-impl Display for Display1in54 {
+impl<B:AsRef<[u8]>+AsMut<[u8]>> Display for Display1in54<B> {
     fn clear_buffer(&mut self,color:Color) {
         let color=self.get_color_bits(color);
+        let buffer=self.planes();
         if color.0 {
-            self.buffer.0.fill(0xff);
+            buffer.0.fill(0xff);
         } else {
-            self.buffer.0.fill(0);
+            buffer.0.fill(0);
         }
         if color.1 {
-            self.buffer.1.fill(0xff);
+            buffer.1.fill(0xff);
         } else {
-            self.buffer.1.fill(0);
+            buffer.1.fill(0);
         }
     }
     fn invert_display(&mut self) {
         self.inverted=!self.inverted;
-        for (c1,c2) in self.buffer.0.iter_mut().zip(self.buffer.1.iter_mut()) {
+        let buffer=self.planes();
+        for (c1,c2) in buffer.0.iter_mut().zip(buffer.1.iter_mut()) {
             *c1=!*c1;
             *c2=!*c2;
         }
     }
+    fn is_inverted(&self)->bool {
+        self.inverted
+    }
     fn buffer1(&self)->&[u8] {
-        &self.buffer.0
+        self.buffer.0.as_ref()
     }
     fn buffer2(&self)->&[u8] {
-        &self.buffer.1
+        self.buffer.1.as_ref()
     }
     fn buffers(&self)->(&[u8],&[u8]) {
-        (&self.buffer.0,&self.buffer.1)
+        (self.buffer.0.as_ref(),self.buffer.1.as_ref())
     }
     fn set_rotation(&mut self, rotation: DisplayRotation) {
         self.rotation = rotation;
@@ -184,4 +542,109 @@ impl Display for Display1in54 {
     fn rotation(&self) -> DisplayRotation {
         self.rotation
     }
+    fn blit(&mut self,image:&PackedImage,position:Point) {
+        let (rotation,inverted)=(self.rotation,self.inverted);
+        blit_planes(self.planes(),rotation,inverted,image,position);
+    }
+}
+
+/// Length of each buffer of a `width`x`height` [`PanelDisplay`] in bytes
+pub const fn panel_buffer_len(width:u32,height:u32)->usize {
+    (width as usize).div_ceil(8)*height as usize
+}
+
+/// Display for a panel of any size, e.g. the 122x250 panel of 2.13" displays on an SSD1680
+///
+/// Rows are padded to whole bytes like the rows of the RAM, so the buffers fit a driver
+/// configured with [`Builder::panel_size`](crate::config::Builder::panel_size).
+pub struct PanelDisplay<B> {
+    buffer:(B,B),
+    size:(usize,usize),
+    rotation:DisplayRotation,
+    inverted:bool,
+}
+#[cfg(feature="alloc")]
+impl PanelDisplay<Vec<u8>> {
+    /// Create a display buffer for a `width`x`height` panel
+    pub fn new(width:u32,height:u32)->Self {
+        let len=panel_buffer_len(width,height);
+        PanelDisplay::with_buffers(width,height,vec![0xff;len],vec![0xff;len])
+    }
+}
+impl<B:AsRef<[u8]>+AsMut<[u8]>> PanelDisplay<B> {
+    /// Create a display for a `width`x`height` panel in buffers provided by the caller. The
+    /// buffers are reset like those of [`Display1in54::new`].
+    ///
+    /// # Panics
+    ///
+    /// If a buffer isn't [`panel_buffer_len`] bytes long.
+    pub fn with_buffers(width:u32,height:u32,mut buffer1:B,mut buffer2:B)->Self {
+        let len=panel_buffer_len(width,height);
+        assert!(buffer1.as_ref().len()==len&&buffer2.as_ref().len()==len,"buffers don't match the panel size");
+        buffer1.as_mut().fill(0xff);
+        buffer2.as_mut().fill(0xff);
+        PanelDisplay {
+            buffer:(buffer1,buffer2),
+            size:(width as usize,height as usize),
+            rotation:DisplayRotation::default(),
+            inverted:false,
+        }
+    }
+}
+impl<B:AsRef<[u8]>+AsMut<[u8]>> DrawTarget for PanelDisplay<B> {
+    type Color=Color;
+    type Error=core::convert::Infallible;
+    fn draw_iter<I:IntoIterator<Item=Pixel<Color>>>(&mut self,pixels:I)->Result<(),Self::Error> {
+        let (width,height)=rotated_size(self.rotation,self.size);
+        let stride=self.size.0.div_ceil(8);
+        for Pixel(point,color) in pixels {
+            if point.x<0||point.y<0||point.x>=width as i32||point.y>=height as i32 {
+                continue;
+            }
+            let (x,y)=rotate_point(self.rotation,self.size,point.x as usize,point.y as usize);
+            let luma=color.luma();
+            let idx=y*stride+x/8;
+            let bit=0b10000000>>(x%8);
+            for (plane,set) in [(self.buffer.0.as_mut(),luma&1==1),(self.buffer.1.as_mut(),luma&2==2)] {
+                if set!=self.inverted {
+                    plane[idx]|=bit;
+                } else {
+                    plane[idx]&=!bit;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn clear(&mut self,color:Color)->Result<(),Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+impl<B> Dimensions for PanelDisplay<B> {
+    fn bounding_box(&self)->Rectangle {
+        let (width,height)=rotated_size(self.rotation,self.size);
+        Rectangle::new(Point::zero(),Size::new(width as u32,height as u32))
+    }
+}
+impl<B:AsRef<[u8]>+AsMut<[u8]>> Display for PanelDisplay<B> {
+    fn clear_buffer(&mut self,color:Color) {
+        let luma=color.luma();
+        let inverted=self.inverted;
+        for (plane,set) in [(self.buffer.0.as_mut(),luma&1==1),(self.buffer.1.as_mut(),luma&2==2)] {
+            plane.fill(if set!=inverted {0xff} else {0});
+        }
+    }
+    fn invert_display(&mut self) {
+        self.inverted^=true;
+        for plane in [self.buffer.0.as_mut(),self.buffer.1.as_mut()] {
+            plane.iter_mut().for_each(|byte|*byte^=0xff);
+        }
+    }
+    fn is_inverted(&self)->bool {self.inverted}
+    fn panel_size(&self)->Size {Size::new(self.size.0 as u32,self.size.1 as u32)}
+    fn buffer1(&self)->&[u8] {self.buffer.0.as_ref()}
+    fn buffer2(&self)->&[u8] {self.buffer.1.as_ref()}
+    fn buffers(&self)->(&[u8],&[u8]) {(self.buffer.0.as_ref(),self.buffer.1.as_ref())}
+    fn set_rotation(&mut self,rotation:DisplayRotation) {self.rotation=rotation}
+    fn rotation(&self)->DisplayRotation {self.rotation}
 }
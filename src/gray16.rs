@@ -0,0 +1,137 @@
+//! Experimental 16 level grayscale
+//!
+//! [`Display1in54Gray16`] keeps 4 bits per pixel and is drawn with [`Gray4`] colors. The panel
+//! can only drive pixels towards black or white, so
+//! [`Ssd1681::display_gray16`](crate::driver::Ssd1681::display_gray16) clears the panel to black
+//! and then lightens it in four passes, one for each bit of the luma of the pixels. Every pass
+//! loads a waveform that drives the pixels with that bit set towards white for the number of
+//! frames given for the pass, by default twice as long as for the previous bit, so the drive
+//! times of a pixel add up in proportion to its luma.
+//!
+//! How many levels can be told apart depends on the panel and the temperature; the pass lengths
+//! can be tuned by passing other frame counts than [`DEFAULT_PASS_FRAMES`].
+//!
+//! **WARNING! EXPERIMENTAL AND MAY BREAK YOUR DISPLAY**
+
+#[cfg(feature="alloc")]
+use alloc::vec::Vec;
+use embedded_graphics_core::{
+    geometry::{Dimensions, Point, Size},
+    pixelcolor::{Gray4, GrayColor},
+    draw_target::DrawTarget,
+    primitives::Rectangle,
+    Pixel,
+};
+use crate::{
+    driver::{Waveform, FULL_WAVEFORM, GRAY4_WAVEFORM},
+    graphics::{rotate_point, rotated_size, DisplayRotation},
+    HEIGHT,
+    WIDTH,
+};
+
+/// Frames each pass drives towards white, from the lowest bit of the luma to the highest
+pub const DEFAULT_PASS_FRAMES:[u8;4]=[3,6,12,24];
+
+/// VS byte that drives VSL (white) in phase A and holds VSS in phases B to D, like the last phase
+/// of LUT1 of [`FULL_WAVEFORM`]
+const DRIVE_WHITE:u8=0x80;
+
+/// Waveform of one pass: pixels with their black/white RAM bit set (LUT1 and LUT3, whatever the
+/// red bit) are driven towards white for `frames` frames, all other pixels are left alone
+pub(crate) fn pass_waveform(frames:u8)->Waveform {
+    debug_assert_eq!(FULL_WAVEFORM[14]&0xc0,DRIVE_WHITE);
+    let mut waveform=[0;159];
+    waveform[12]=DRIVE_WHITE;
+    waveform[36]=DRIVE_WHITE;
+    // group 0: only phase A, not repeated
+    waveform[60]=frames;
+    // frame rate
+    waveform[144..150].fill(0x22);
+    // end option and voltages like the gray4 waveform
+    waveform[153..].copy_from_slice(&GRAY4_WAVEFORM[153..]);
+    waveform
+}
+
+/// Length of the buffer of a [`Display1in54Gray16`] in bytes
+pub const GRAY16_BUFFER_LEN:usize=(WIDTH*HEIGHT)/2;
+
+/// Buffer storage of [`Display1in54Gray16::new`]: a `Vec` with the `alloc` feature, an inline
+/// array without it
+#[cfg(feature="alloc")]
+pub type Gray16Buffer=Vec<u8>;
+/// Buffer storage of [`Display1in54Gray16::new`]: a `Vec` with the `alloc` feature, an inline
+/// array without it
+#[cfg(not(feature="alloc"))]
+pub type Gray16Buffer=[u8;GRAY16_BUFFER_LEN];
+
+/// Display for a 200x200 panel with 16 gray levels
+///
+/// Pixels are stored row by row in panel coordinates, two per byte with the left one in the high
+/// nibble. Each nibble is the luma of a [`Gray4`], 0 being black and 15 white.
+pub struct Display1in54Gray16<B=Gray16Buffer> {
+    buffer:B,
+    rotation:DisplayRotation,
+}
+impl Display1in54Gray16 {
+    /// Creates a white display
+    pub fn new()->Self {
+        Display1in54Gray16 {
+            #[cfg(not(feature="alloc"))]
+            buffer:[0xff;GRAY16_BUFFER_LEN],
+            #[cfg(feature="alloc")]
+            buffer:vec![0xff;GRAY16_BUFFER_LEN],
+            rotation:DisplayRotation::default(),
+        }
+    }
+}
+impl Default for Display1in54Gray16 {
+    fn default()->Self {Self::new()}
+}
+impl<'a> Display1in54Gray16<&'a mut [u8;GRAY16_BUFFER_LEN]> {
+    /// Creates a white display in a buffer provided by the caller
+    pub fn with_buffer(buffer:&'a mut [u8;GRAY16_BUFFER_LEN])->Self {
+        buffer.fill(0xff);
+        Display1in54Gray16 {buffer,rotation:DisplayRotation::default()}
+    }
+}
+impl<B:AsRef<[u8]>+AsMut<[u8]>> Display1in54Gray16<B> {
+    /// The buffer
+    pub fn buffer(&self)->&[u8] {self.buffer.as_ref()}
+    /// Sets the rotation of the display
+    pub fn set_rotation(&mut self,rotation:DisplayRotation) {self.rotation=rotation}
+    /// Gets the rotation of the display
+    pub fn rotation(&self)->DisplayRotation {self.rotation}
+    /// Luma (0-15) of the pixel at `x`, `y` in panel coordinates
+    pub fn luma(&self,x:usize,y:usize)->u8 {
+        let idx=x+y*WIDTH;
+        (self.buffer.as_ref()[idx/2]>>(4-(idx%2)*4))&0x0f
+    }
+}
+impl<B:AsRef<[u8]>+AsMut<[u8]>> DrawTarget for Display1in54Gray16<B> {
+    type Color=Gray4;
+    type Error=core::convert::Infallible;
+    fn draw_iter<I:IntoIterator<Item=Pixel<Gray4>>>(&mut self,pixels:I)->Result<(),Self::Error> {
+        let (width,height)=rotated_size(self.rotation,(WIDTH,HEIGHT));
+        for Pixel(point,color) in pixels {
+            if point.x<0||point.y<0||point.x>=width as i32||point.y>=height as i32 {
+                continue;
+            }
+            let (x,y)=rotate_point(self.rotation,(WIDTH,HEIGHT),point.x as usize,point.y as usize);
+            let idx=x+y*WIDTH;
+            let shift=4-(idx%2)*4;
+            let byte=&mut self.buffer.as_mut()[idx/2];
+            *byte=(*byte&!(0x0f<<shift))|(color.luma()<<shift);
+        }
+        Ok(())
+    }
+    fn clear(&mut self,color:Gray4)->Result<(),Self::Error> {
+        self.buffer.as_mut().fill(color.luma()<<4|color.luma());
+        Ok(())
+    }
+}
+impl<B> Dimensions for Display1in54Gray16<B> {
+    fn bounding_box(&self)->Rectangle {
+        let (width,height)=rotated_size(self.rotation,(WIDTH,HEIGHT));
+        Rectangle::new(Point::zero(),Size::new(width as u32,height as u32))
+    }
+}
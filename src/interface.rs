@@ -7,33 +7,46 @@ use embedded_hal::{
     spi::blocking::{Write, Transactional, Operation},
     digital::blocking::{InputPin, OutputPin},
 };
+use crate::driver::{Error, DEFAULT_BUSY_TIMEOUT_MS};
 
 const RESET_DELAY_MS: u32 = 10;
+/// Size of the buffer `data_mapped` and `data_x_times` stream from. Each chunk is one SPI write,
+/// in one contiguous buffer so DMA capable SPI implementations can send it in one go.
+const CHUNK_LEN: usize = 128;
+/// Time between two BUSY polls
+const BUSY_POLL_US: u32 = 100;
 
 /// The Connection Interface of all (?) Waveshare EPD-Devices
 ///
-pub(crate) struct DisplayInterface<SPI, BUSY, RST> {
+pub(crate) struct DisplayInterface<SPI, BUSY, RST, DELAY> {
     /// SPI
     _spi: PhantomData<SPI>,
     /// Low for busy, Wait until display is ready!
     busy: BUSY,
     /// Pin for Reseting
     rst: RST,
+    /// Delay for resets and between BUSY polls
+    delay: DELAY,
+    /// Longest wait for BUSY in ms, 0 waits forever
+    pub(crate) busy_timeout_ms: u32,
 }
 
-impl<SPI, BUSY, RST> DisplayInterface<SPI, BUSY, RST>
+impl<SPI, BUSY, RST, DELAY> DisplayInterface<SPI, BUSY, RST, DELAY>
 where
     SPI: Write + Transactional,
     BUSY: InputPin,
     RST: OutputPin,
     RST::Error: Debug,
+    DELAY: DelayUs,
 {
     /// Create and initialize display
-    pub fn new(busy: BUSY, rst: RST) -> Self {
+    pub fn new(busy: BUSY, rst: RST, delay: DELAY) -> Self {
         DisplayInterface {
             _spi: PhantomData,
             busy,
             rst,
+            delay,
+            busy_timeout_ms: DEFAULT_BUSY_TIMEOUT_MS,
         }
     }
 
@@ -58,6 +71,33 @@ where
         self.data(spi, data)
     }
 
+    /// Sends a command and reads the bytes the controller answers with
+    pub(crate) fn cmd_read(
+        &mut self,
+        spi: &mut SPI,
+        command: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), SPI::Error> {
+        spi.exec(&mut [Operation::Transfer(&mut [0], &[command]), Operation::Read(buffer)])
+    }
+
+    /// Sends `data` with `map` applied to every byte, a small chunk at a time
+    pub(crate) fn data_mapped(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+        map: impl Fn(u8) -> u8,
+    ) -> Result<(), SPI::Error> {
+        let mut chunk = [0; CHUNK_LEN];
+        for part in data.chunks(CHUNK_LEN) {
+            for (out, byte) in chunk.iter_mut().zip(part) {
+                *out = map(*byte);
+            }
+            self.data(spi, &chunk[..part.len()])?;
+        }
+        Ok(())
+    }
+
     /// Basic function for sending the same byte of data (one u8) multiple times over spi
     /// Used for setting one color for the whole frame
     pub(crate) fn data_x_times(
@@ -66,23 +106,39 @@ where
         val: u8,
         repetitions: u32,
     ) -> Result<(), SPI::Error> {
-        // Transfer data (u8) over spi
-        for _ in 0..repetitions {
-            self.data(spi, &[val])?;
+        let chunk = [val; CHUNK_LEN];
+        let mut remaining = repetitions as usize;
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_LEN);
+            self.data(spi, &chunk[..len])?;
+            remaining -= len;
         }
         Ok(())
     }
 
     /// Waits until device isn't busy anymore (busy == HIGH)
-    pub(crate) fn wait_until_idle(&mut self) {
-        while self.busy.is_high().unwrap_or(true) {}
+    ///
+    /// Returns how long BUSY was high in ms, or [`Error::BusyTimeout`] once it has been high for
+    /// longer than the busy timeout
+    pub(crate) fn wait_until_idle(&mut self) -> Result<u32, Error<SPI::Error>> {
+        let mut waited_us: u64 = 0;
+        while self.busy.is_high().unwrap_or(true) {
+            if self.busy_timeout_ms > 0 && waited_us >= self.busy_timeout_ms as u64 * 1000 {
+                warn!("BUSY still high after {} ms", self.busy_timeout_ms);
+                return Err(Error::BusyTimeout);
+            }
+            self.delay.delay_us(BUSY_POLL_US).map_err(|_| Error::Delay)?;
+            waited_us += BUSY_POLL_US as u64;
+        }
+        Ok((waited_us / 1000) as u32)
     }
 
     /// Resets the device.
-    pub(crate) fn reset<DELAY: DelayUs>(&mut self, delay: &mut DELAY)->Result<(),DELAY::Error> {
-        self.rst.set_low().unwrap();
-        delay.delay_ms(RESET_DELAY_MS)?;
-        self.rst.set_high().unwrap();
-        delay.delay_ms(RESET_DELAY_MS)
+    pub(crate) fn reset(&mut self) -> Result<(), Error<SPI::Error>> {
+        debug!("hardware reset");
+        self.rst.set_low().map_err(|_| Error::Reset)?;
+        self.delay.delay_ms(RESET_DELAY_MS).map_err(|_| Error::Delay)?;
+        self.rst.set_high().map_err(|_| Error::Reset)?;
+        self.delay.delay_ms(RESET_DELAY_MS).map_err(|_| Error::Delay)
     }
 }
@@ -19,7 +19,7 @@
 //! 3. then kick off a display update using [`driver::Ssd1681::display_frame`]
 
 
-#![no_std]
+#![cfg_attr(not(feature="std"),no_std)]
 #![deny(missing_docs)]
 #![allow(clippy::pedantic)]
 #![allow(clippy::cast_possible_truncation)]
@@ -31,16 +31,33 @@
 #[macro_use]
 extern crate alloc;
 
+#[macro_use]
+mod fmt;
 
 pub mod graphics;
 pub mod driver;
+pub mod controller;
+pub mod config;
+pub mod analog;
+pub mod diff;
+pub mod gray16;
+pub mod plan;
 pub mod interface;
+#[cfg(feature="trace")]
+pub mod trace;
+#[cfg(feature="snapshot")]
+pub mod snapshot;
+#[cfg(feature="emulator")]
+pub mod emulator;
+#[cfg(feature="otp")]
+pub mod otp;
 /// Useful exports
 pub mod prelude {
     pub use crate::color::Color;
+    pub use crate::config::Builder as ConfigBuilder;
     pub use crate::driver::Ssd1681;
 
-    pub use crate::graphics::{Display, Display1in54, DisplayRotation};
+    pub use crate::graphics::{Band, Display, Display1in54, DisplayRotation, PackedFormat, PackedImage, PanelDisplay};
 }
 /// Reexports of embedded graphics [`Gray2`] definitions
 pub mod color {
@@ -76,14 +93,112 @@ mod cmd {
     pub const WRITE_BUFFER2_DATA: u8 = 0x26;
     pub const UPDATE_DISPLAY_CTRL2: u8 = 0x22;
     pub const MASTER_ACTIVATE: u8 = 0x20;
+
+    // LUT
+    pub const WRITE_LUT: u8 = 0x32;
+    pub const END_OPTION: u8 = 0x3F;
+    pub const GATE_VOLTAGE: u8 = 0x03;
+    pub const SOURCE_VOLTAGE: u8 = 0x04;
+    pub const WRITE_VCOM: u8 = 0x2C;
+
+    // Analog
+    pub const BOOSTER_SOFT_START_CONTROL: u8 = 0x0C;
+
+    // Supply
+    pub const HV_READY_DETECTION: u8 = 0x14;
+    pub const VCI_DETECTION: u8 = 0x15;
+
+    // Identification
+    pub const READ_USER_ID: u8 = 0x2E;
+    pub const READ_STATUS: u8 = 0x2F;
+    pub const WRITE_USER_ID: u8 = 0x38;
+
+    // OTP
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const VCOM_SENSE: u8 = 0x28;
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const VCOM_SENSE_DURATION: u8 = 0x29;
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const PROGRAM_VCOM_OTP: u8 = 0x2A;
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const READ_DISPLAY_OPTION: u8 = 0x2D;
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const PROGRAM_WS_OTP: u8 = 0x30;
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const PROGRAM_OTP_SELECTION: u8 = 0x36;
+    #[cfg(any(feature="otp",feature="emulator"))]
+    pub const WRITE_DISPLAY_OPTION: u8 = 0x37;
+
+    /// Returns the name of a command, for debugging output
+    #[cfg(feature="trace")]
+    pub fn name(command: u8) -> Option<&'static str> {
+        Some(match command {
+            SW_RESET => "SW_RESET",
+            DRIVER_CONTROL => "DRIVER_CONTROL",
+            DATA_ENTRY_MODE => "DATA_ENTRY_MODE",
+            SET_RAMXPOS => "SET_RAMXPOS",
+            SET_RAMYPOS => "SET_RAMYPOS",
+            BORDER_WAVEFORM_CONTROL => "BORDER_WAVEFORM_CONTROL",
+            TEMP_CONTROL => "TEMP_CONTROL",
+            SET_RAMX_COUNTER => "SET_RAMX_COUNTER",
+            SET_RAMY_COUNTER => "SET_RAMY_COUNTER",
+            WRITE_BUFFER1_DATA => "WRITE_BUFFER1_DATA",
+            WRITE_BUFFER2_DATA => "WRITE_BUFFER2_DATA",
+            UPDATE_DISPLAY_CTRL2 => "UPDATE_DISPLAY_CTRL2",
+            MASTER_ACTIVATE => "MASTER_ACTIVATE",
+            WRITE_LUT => "WRITE_LUT",
+            END_OPTION => "END_OPTION",
+            GATE_VOLTAGE => "GATE_VOLTAGE",
+            SOURCE_VOLTAGE => "SOURCE_VOLTAGE",
+            WRITE_VCOM => "WRITE_VCOM",
+            BOOSTER_SOFT_START_CONTROL => "BOOSTER_SOFT_START_CONTROL",
+            HV_READY_DETECTION => "HV_READY_DETECTION",
+            VCI_DETECTION => "VCI_DETECTION",
+            READ_USER_ID => "READ_USER_ID",
+            READ_STATUS => "READ_STATUS",
+            WRITE_USER_ID => "WRITE_USER_ID",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            VCOM_SENSE => "VCOM_SENSE",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            VCOM_SENSE_DURATION => "VCOM_SENSE_DURATION",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            PROGRAM_VCOM_OTP => "PROGRAM_VCOM_OTP",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            READ_DISPLAY_OPTION => "READ_DISPLAY_OPTION",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            PROGRAM_WS_OTP => "PROGRAM_WS_OTP",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            PROGRAM_OTP_SELECTION => "PROGRAM_OTP_SELECTION",
+            #[cfg(any(feature="otp",feature="emulator"))]
+            WRITE_DISPLAY_OPTION => "WRITE_DISPLAY_OPTION",
+            _ => return None,
+        })
+    }
 }
 mod flag {
-    pub const DATA_ENTRY_INCRY_INCRX: u8 = 0b11;
+    pub const DATA_ENTRY_INCRX: u8 = 0b001;
+    pub const DATA_ENTRY_INCRY: u8 = 0b010;
+    pub const DATA_ENTRY_Y_FIRST: u8 = 0b100;
     pub const INTERNAL_TEMP_SENSOR: u8 = 0x80;
+    pub const EXTERNAL_TEMP_SENSOR: u8 = 0x48;
     pub const BORDER_WAVEFORM_FOLLOW_LUT: u8 = 0b0100;
     pub const BORDER_WAVEFORM_LUT1: u8 = 0b0001;
+    pub const BORDER_WAVEFORM_LUT_MASK: u8 = 0b0011;
+    pub const BORDER_WAVEFORM_FIX_LEVEL: u8 = 0b0100_0000;
+    pub const BORDER_WAVEFORM_VCOM: u8 = 0b1000_0000;
+    pub const BORDER_WAVEFORM_HIZ: u8 = 0b1100_0000;
+    pub const BORDER_WAVEFORM_VSS: u8 = 0b0000_0000;
+    pub const BORDER_WAVEFORM_VSH1: u8 = 0b0001_0000;
+    pub const BORDER_WAVEFORM_VSL: u8 = 0b0010_0000;
+    pub const BORDER_WAVEFORM_VSH2: u8 = 0b0011_0000;
     pub const BW_DISPLAY_MODE_1: u8 = 0xF7;
     pub const GRAY4_DISPLAY_MODE_1: u8 = 0xC7;  // required
+    pub const CLOCK_ANALOG_ON: u8 = 0xC0;
+    pub const CLOCK_ANALOG_OFF: u8 = 0x03;
+    pub const STATUS_HV_NOT_READY: u8 = 0b0010_0000;
+    pub const STATUS_VCI_LOW: u8 = 0b0001_0000;
+    pub const STATUS_BUSY: u8 = 0b0000_0100;
+    pub const STATUS_CHIP_ID: u8 = 0b0000_0011;
 }
 
 /// Maximum display height this driver supports
@@ -0,0 +1,147 @@
+//! Reading and programming the controller's one time programmable memory
+//!
+//! Requires the `otp` feature. The OTP holds the waveforms, the VCOM value and the display
+//! options the controller falls back to without a custom LUT. Programming it can't be undone and
+//! only works a limited number of times, so every write takes an [`Irreversible`] token that has
+//! to be created explicitly for that one write. Check that the panel module supports OTP
+//! programming before using it.
+//!
+//! A calibration run usually looks like this:
+//!
+//! ```ignore
+//! let mut otp=ssd1681.otp();
+//! let vcom=otp.sense_vcom(&mut spi,10)?;
+//! otp.program_vcom(&mut spi,vcom,Irreversible::i_understand_otp_writes_are_permanent())?;
+//! ```
+
+use core::fmt::Debug;
+use embedded_hal::{
+    spi::blocking::{Write, Transactional},
+    delay::blocking::DelayUs,
+    digital::blocking::{InputPin, OutputPin},
+};
+use crate::{
+    analog::{OutOfRange, Vcom},
+    controller::Controller,
+    driver::{Error, Ssd1681},
+    cmd,
+    flag,
+};
+
+/// Confirms that one OTP write is intended. Every write consumes a token.
+#[derive(Debug)]
+pub struct Irreversible(());
+impl Irreversible {
+    /// Creates a token for a single OTP write, which permanently changes the controller
+    pub fn i_understand_otp_writes_are_permanent()->Self {Irreversible(())}
+}
+
+/// Display options stored in OTP
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature="defmt",derive(defmt::Format))]
+pub struct DisplayOption {
+    /// Selects the spare VCOM OTP (bit 7)
+    pub vcom_selection:u8,
+    /// The VCOM register. Only read, it is programmed with [`Otp::program_vcom`].
+    pub vcom:u8,
+    /// Display mode (ping-pong) of each waveform setting
+    pub display_mode:[u8;5],
+    /// Waveform version
+    pub waveform_version:[u8;4],
+}
+
+/// Access to the OTP of a controller, see the [module documentation](self)
+pub struct Otp<'a,SPI,BUSY,RST,DELAY,C> {
+    driver:&'a mut Ssd1681<SPI,BUSY,RST,DELAY,C>,
+}
+
+impl<SPI,BUSY,RST,DELAY,C> Ssd1681<SPI,BUSY,RST,DELAY,C>
+where
+    SPI: Write + Transactional,
+    BUSY: InputPin,
+    RST: OutputPin,
+    RST::Error: Debug,
+    DELAY: DelayUs,
+    C: Controller,
+{
+    /// Access to the one time programmable memory
+    pub fn otp(&mut self)->Otp<'_,SPI,BUSY,RST,DELAY,C> {Otp {driver:self}}
+}
+
+impl<SPI,BUSY,RST,DELAY,C> Otp<'_,SPI,BUSY,RST,DELAY,C>
+where
+    SPI: Write + Transactional,
+    BUSY: InputPin,
+    RST: OutputPin,
+    RST::Error: Debug,
+    DELAY: DelayUs,
+    C: Controller,
+{
+    /// Reads the display options and the VCOM register
+    pub fn read_display_option(&mut self,spi:&mut SPI)->Result<DisplayOption,Error<SPI::Error>> {
+        let mut data=[0;11];
+        self.driver.interface.cmd_read(spi,cmd::READ_DISPLAY_OPTION,&mut data)?;
+        let mut option=DisplayOption {vcom_selection:data[0],vcom:data[1],..Default::default()};
+        option.display_mode.copy_from_slice(&data[2..7]);
+        option.waveform_version.copy_from_slice(&data[7..11]);
+        Ok(option)
+    }
+    /// Measures VCOM on the panel for `seconds` (1-16) and returns the result, which is also
+    /// left in the VCOM register.
+    ///
+    /// Fails with [`Error::OutOfRange`] before sensing if `seconds` isn't 1-16, and after it if
+    /// the controller reports a VCOM that isn't valid.
+    pub fn sense_vcom(&mut self,spi:&mut SPI,seconds:u8)->Result<Vcom,Error<SPI::Error>> {
+        if !(1..=16).contains(&seconds) {
+            return Err(Error::OutOfRange(OutOfRange {min:1,max:16}));
+        }
+        debug!("otp: sensing vcom for {}s",seconds);
+        let interface=&mut self.driver.interface;
+        // sensing needs the clock and the analog circuits running
+        interface.cmd_with_data(spi,cmd::UPDATE_DISPLAY_CTRL2,&[flag::CLOCK_ANALOG_ON])?;
+        interface.cmd(spi,cmd::MASTER_ACTIVATE)?;
+        interface.wait_until_idle()?;
+        interface.cmd_with_data(spi,cmd::VCOM_SENSE_DURATION,&[seconds-1])?;
+        interface.cmd(spi,cmd::VCOM_SENSE)?;
+        interface.wait_until_idle()?;
+        let vcom=Vcom::from_code(self.read_display_option(spi)?.vcom).map_err(Error::OutOfRange)?;
+        info!("otp: sensed vcom {}mV",vcom.millivolts());
+        Ok(vcom)
+    }
+    /// Writes `vcom` to the VCOM register and programs it into OTP
+    pub fn program_vcom(&mut self,spi:&mut SPI,vcom:Vcom,_confirm:Irreversible)->Result<(),Error<SPI::Error>> {
+        warn!("otp: programming vcom {}mV",vcom.millivolts());
+        let interface=&mut self.driver.interface;
+        interface.cmd_with_data(spi,cmd::WRITE_VCOM,&[vcom.code()])?;
+        interface.cmd(spi,cmd::PROGRAM_VCOM_OTP)?;
+        interface.wait_until_idle()?;
+        Ok(())
+    }
+    /// Writes the display option register and programs it into OTP. The `vcom` field is ignored.
+    pub fn program_display_option(
+        &mut self,
+        spi:&mut SPI,
+        option:&DisplayOption,
+        _confirm:Irreversible,
+    )->Result<(),Error<SPI::Error>> {
+        warn!("otp: programming display option");
+        let mut data=[0;10];
+        data[0]=option.vcom_selection;
+        data[1..6].copy_from_slice(&option.display_mode);
+        data[6..10].copy_from_slice(&option.waveform_version);
+        let interface=&mut self.driver.interface;
+        interface.cmd_with_data(spi,cmd::WRITE_DISPLAY_OPTION,&data)?;
+        interface.cmd(spi,cmd::PROGRAM_OTP_SELECTION)?;
+        interface.wait_until_idle()?;
+        Ok(())
+    }
+    /// Programs the waveform in the LUT register, as loaded by
+    /// [`set_lut`](Ssd1681::set_lut), into the waveform OTP
+    pub fn program_waveform(&mut self,spi:&mut SPI,_confirm:Irreversible)->Result<(),Error<SPI::Error>> {
+        warn!("otp: programming waveform ({:?})",self.driver.current_lut_type());
+        let interface=&mut self.driver.interface;
+        interface.cmd(spi,cmd::PROGRAM_WS_OTP)?;
+        interface.wait_until_idle()?;
+        Ok(())
+    }
+}
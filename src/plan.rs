@@ -0,0 +1,175 @@
+//! Planning uploads for several dirty areas
+//!
+//! [`plan`] turns a list of dirty rectangles into the areas to upload with
+//! [`Ssd1681::update_area`]. The rectangles are widened to whole bytes and clipped to the
+//! panel, then overlapping ones are merged, as are neighbours where one bigger upload is cheaper
+//! than two small ones according to a [`CostModel`]. If uploading the areas would cost more than
+//! uploading the whole frame, the plan uploads the whole frame instead.
+//!
+//! The rectangles are in the coordinates of a panel of the size given to [`plan`], usually
+//! [`Ssd1681::panel_size`]. Areas drawn on a rotated display can be mapped with
+//! [`DisplayRotation::to_panel`](crate::graphics::DisplayRotation::to_panel).
+//!
+//! The SSD1681 always refreshes the whole panel, so every plan that uploads anything ends with a
+//! single refresh. Splitting the uploads over several refreshes can't save anything, which is why
+//! the [`CostModel`] only weighs SPI bytes against window setups.
+
+use core::fmt::Debug;
+use embedded_hal::{
+    spi::blocking::{Write, Transactional},
+    delay::blocking::DelayUs,
+    digital::blocking::{InputPin, OutputPin},
+};
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+use crate::{
+    controller::{align_to_bytes, Controller},
+    driver::{Error, Ssd1681},
+    graphics::Display,
+};
+
+/// Relative costs used to compare plans
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct CostModel {
+    /// Cost of one byte of RAM data. Every area is written to both RAMs.
+    pub byte:u32,
+    /// Cost of starting the upload of an area: setting the RAM window and address counters takes
+    /// about 16 bytes in 8 transactions for each RAM
+    pub area:u32,
+}
+impl Default for CostModel {
+    fn default()->Self {
+        CostModel {byte:1,area:32}
+    }
+}
+impl CostModel {
+    /// Cost of uploading `area` to both RAMs
+    pub fn upload(&self,area:&Rectangle)->u32 {
+        if area.is_zero_sized() {
+            return 0;
+        }
+        let bytes=area.size.width/8*area.size.height*2;
+        bytes*self.byte+self.area
+    }
+}
+
+/// Clips `area` to a `panel` sized panel and widens it to whole bytes
+fn align(area:&Rectangle,panel:Size)->Rectangle {align_to_bytes(area,panel.width,panel.height)}
+
+/// Smallest rectangle containing both `a` and `b`
+fn union(a:&Rectangle,b:&Rectangle)->Rectangle {
+    let left=a.top_left.x.min(b.top_left.x);
+    let top=a.top_left.y.min(b.top_left.y);
+    let right=(a.top_left.x+a.size.width as i32).max(b.top_left.x+b.size.width as i32);
+    let bottom=(a.top_left.y+a.size.height as i32).max(b.top_left.y+b.size.height as i32);
+    Rectangle::new(Point::new(left,top),Size::new((right-left) as u32,(bottom-top) as u32))
+}
+
+/// Uploads and refreshes for a set of dirty areas, holding at most `N` areas
+#[derive(Clone,Debug,PartialEq)]
+pub struct Plan<const N:usize> {
+    areas:[Rectangle;N],
+    len:usize,
+    cost:u32,
+    /// The whole panel, aligned to bytes
+    full:Rectangle,
+}
+impl<const N:usize> Plan<N> {
+    /// The areas to upload, in panel coordinates and aligned to bytes
+    pub fn areas(&self)->&[Rectangle] {&self.areas[..self.len]}
+    /// Returns `true` if there is nothing to upload
+    pub fn is_empty(&self)->bool {self.len==0}
+    /// Returns `true` if the plan uploads the whole frame
+    pub fn is_full_frame(&self)->bool {
+        self.len==1&&self.areas[0]==self.full
+    }
+    /// Number of refreshes the plan needs
+    pub fn refreshes(&self)->u32 {
+        if self.is_empty() {0} else {1}
+    }
+    /// Cost of the plan according to the model it was made with
+    pub fn cost(&self)->u32 {self.cost}
+    /// Uploads the areas of `display` and refreshes the panel with the driver's current LUT
+    pub fn execute<SPI,BUSY,RST,DELAY,C,D>(&self,driver:&mut Ssd1681<SPI,BUSY,RST,DELAY,C>,spi:&mut SPI,display:&D)->Result<(),Error<SPI::Error>>
+    where
+        SPI: Write + Transactional,
+        BUSY: InputPin,
+        RST: OutputPin,
+        RST::Error: Debug,
+        DELAY: DelayUs,
+        C: Controller,
+        D: Display,
+    {
+        if self.is_empty() {
+            return Ok(());
+        }
+        for area in self.areas() {
+            driver.update_area(spi,display,*area)?;
+        }
+        driver.display_window(spi)
+    }
+
+    /// Merges the pair of areas that gains the most, or loses the least if `force` is set.
+    /// Returns `false` if no pair was merged.
+    fn merge_best(&mut self,cost:&CostModel,force:bool)->bool {
+        if self.len<2 {
+            return false;
+        }
+        let mut best=(0,1,i64::MIN);
+        for i in 0..self.len {
+            for j in i+1..self.len {
+                let (a,b)=(&self.areas[i],&self.areas[j]);
+                let merged=union(a,b);
+                let overlap=!a.intersection(b).is_zero_sized();
+                // overlapping areas would be uploaded twice, always merge them
+                let gain=if overlap {
+                    i64::MAX
+                } else {
+                    (cost.upload(a)+cost.upload(b)) as i64-cost.upload(&merged) as i64
+                };
+                if gain>best.2 {
+                    best=(i,j,gain);
+                }
+            }
+        }
+        let (i,j,gain)=best;
+        if gain<0&&!force {
+            return false;
+        }
+        self.areas[i]=union(&self.areas[i],&self.areas[j]);
+        self.len-=1;
+        self.areas[j]=self.areas[self.len];
+        true
+    }
+}
+
+/// Plans the uploads for `dirty` areas of a `panel` sized panel, see the
+/// [module documentation](self).
+///
+/// If more than `N` areas would be left, the ones that are cheapest to merge are merged.
+pub fn plan<const N:usize>(dirty:&[Rectangle],panel:Size,cost:&CostModel)->Plan<N> {
+    assert!(N>0);
+    let full=align(&Rectangle::new(Point::zero(),panel),panel);
+    let mut plan=Plan {areas:[Rectangle::zero();N],len:0,cost:0,full};
+    for area in dirty.iter().map(|area|align(area,panel)).filter(|area|!area.is_zero_sized()) {
+        if plan.len==N {
+            plan.merge_best(cost,true);
+        }
+        plan.areas[plan.len]=area;
+        plan.len+=1;
+        while plan.merge_best(cost,false) {}
+    }
+    if plan.is_empty() {
+        return plan;
+    }
+    let areas_cost:u32=plan.areas().iter().map(|area|cost.upload(area)).sum();
+    if cost.upload(&full)<=areas_cost {
+        plan.areas[0]=full;
+        plan.len=1;
+    }
+    plan.cost=plan.areas().iter().map(|area|cost.upload(area)).sum();
+    debug!("plan: {} areas, cost {}",plan.len,plan.cost);
+    plan
+}
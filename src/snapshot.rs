@@ -0,0 +1,335 @@
+//! Host side export of display buffers for snapshot testing
+//!
+//! Requires the `snapshot` feature. A [`Snapshot`] is a copy of what has been drawn to a
+//! [`Display`], in the same (rotated) coordinates that were used for drawing. It can be written
+//! to PNG, PGM or PBM files and compared against a golden image with [`assert_snapshot`].
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    string::String,
+    vec::Vec,
+};
+use crate::{
+    graphics::{plane_luma, rotate_point, rotated_size, Display},
+    HEIGHT,
+    WIDTH,
+};
+
+/// Environment variable that makes [`assert_snapshot`] overwrite the golden images
+pub const UPDATE_ENV:&str="SNAPSHOT_UPDATE";
+
+/// Maps the four [`Gray2`](crate::color::Color) levels to 8 bit gray values
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct GrayMap(pub [u8;4]);
+impl Default for GrayMap {
+    fn default()->Self {GrayMap([0x00,0x55,0xaa,0xff])}
+}
+impl GrayMap {
+    /// Maps an 8 bit gray value back to the closest luma
+    fn luma(&self,gray:u8)->u8 {
+        (0..4).min_by_key(|&l|(self.0[l] as i16-gray as i16).abs()).unwrap() as u8
+    }
+}
+
+/// The contents of a display, one luma value (0-3) per pixel
+#[derive(Clone,Debug,PartialEq)]
+pub struct Snapshot {
+    width:usize,
+    height:usize,
+    luma:Vec<u8>,
+}
+impl Snapshot {
+    /// Captures the buffers of `display` in its current rotation
+    pub fn new<D:Display>(display:&D)->Self {
+        let rotation=display.rotation();
+        let (width,height)=rotated_size(rotation,(WIDTH,HEIGHT));
+        let mut luma=Vec::with_capacity(width*height);
+        for y in 0..height {
+            for x in 0..width {
+                let (px,py)=rotate_point(rotation,(WIDTH,HEIGHT),x,y);
+                luma.push(plane_luma(display.buffers(),display.is_inverted(),px,py));
+            }
+        }
+        Snapshot {width,height,luma}
+    }
+    /// Creates a snapshot from raw luma values
+    pub fn from_luma(width:usize,height:usize,luma:Vec<u8>)->Self {
+        assert_eq!(luma.len(),width*height);
+        assert!(luma.iter().all(|&l|l<4));
+        Snapshot {width,height,luma}
+    }
+    /// Width in pixels
+    pub fn width(&self)->usize {self.width}
+    /// Height in pixels
+    pub fn height(&self)->usize {self.height}
+    /// Luma of the pixel at `x`, `y`
+    pub fn luma(&self,x:usize,y:usize)->u8 {self.luma[x+y*self.width]}
+    /// Drops the gray levels, leaving only black (luma 0) and white (luma 3)
+    pub fn to_binary(&self)->Self {
+        let luma=self.luma.iter().map(|&l|if l>=2 {3} else {0}).collect();
+        Snapshot {width:self.width,height:self.height,luma}
+    }
+    /// Converts the snapshot to 8 bit gray values
+    pub fn to_gray8(&self,map:GrayMap)->Vec<u8> {
+        self.luma.iter().map(|&l|map.0[l as usize]).collect()
+    }
+
+    /// Writes a binary PGM (P5) file
+    pub fn write_pgm<W:Write>(&self,mut out:W,map:GrayMap)->io::Result<()> {
+        write!(out,"P5\n{} {}\n255\n",self.width,self.height)?;
+        out.write_all(&self.to_gray8(map))
+    }
+    /// Writes a binary PBM (P4) file. Dark and light gray are rounded to black and white.
+    pub fn write_pbm<W:Write>(&self,mut out:W)->io::Result<()> {
+        write!(out,"P4\n{} {}\n",self.width,self.height)?;
+        let mut row=vec![0u8;self.width.div_ceil(8)];
+        for y in 0..self.height {
+            row.fill(0);
+            for x in 0..self.width {
+                // PBM uses 1 for black
+                if self.luma(x,y)<2 {
+                    row[x/8]|=0b10000000>>(x%8);
+                }
+            }
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+    /// Writes an 8 bit grayscale PNG file
+    pub fn write_png<W:Write>(&self,out:W,map:GrayMap)->io::Result<()> {
+        let mut encoder=png::Encoder::new(out,self.width as u32,self.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer=encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.to_gray8(map)).map_err(io::Error::other)
+    }
+    /// Saves the snapshot, choosing the format from the file extension (`png`, `pgm` or `pbm`)
+    pub fn save<P:AsRef<Path>>(&self,path:P,map:GrayMap)->io::Result<()> {
+        let path=path.as_ref();
+        let out=io::BufWriter::new(fs::File::create(path)?);
+        match Format::of(path)? {
+            Format::Png=>self.write_png(out,map),
+            Format::Pgm=>self.write_pgm(out,map),
+            Format::Pbm=>self.write_pbm(out),
+        }
+    }
+    /// Loads a snapshot saved with [`Snapshot::save`]
+    pub fn load<P:AsRef<Path>>(path:P,map:GrayMap)->io::Result<Self> {
+        let path=path.as_ref();
+        let input=BufReader::new(fs::File::open(path)?);
+        match Format::of(path)? {
+            Format::Png=>Self::read_png(input,map),
+            Format::Pgm=>Self::read_pnm(input,map,b"P5"),
+            Format::Pbm=>Self::read_pnm(input,map,b"P4"),
+        }
+    }
+    fn read_png<R:Read>(input:R,map:GrayMap)->io::Result<Self> {
+        let mut reader=png::Decoder::new(input).read_info().map_err(io::Error::other)?;
+        let mut gray=vec![0;reader.output_buffer_size()];
+        let info=reader.next_frame(&mut gray).map_err(io::Error::other)?;
+        if info.color_type!=png::ColorType::Grayscale||info.bit_depth!=png::BitDepth::Eight {
+            return Err(invalid("expected an 8 bit grayscale PNG"));
+        }
+        let (width,height)=(info.width as usize,info.height as usize);
+        let luma=gray[..width*height].iter().map(|&g|map.luma(g)).collect();
+        Ok(Snapshot {width,height,luma})
+    }
+    fn read_pnm<R:BufRead>(mut input:R,map:GrayMap,magic:&[u8])->io::Result<Self> {
+        let mut header=Vec::new();
+        let fields=if magic==b"P4" {3} else {4};
+        while header.len()<fields {
+            header.push(read_token(&mut input)?);
+        }
+        if header[0].as_bytes()!=magic {
+            return Err(invalid("unexpected PNM magic number"));
+        }
+        let number=|s:&str|s.parse::<usize>().map_err(|_|invalid("bad PNM header"));
+        let (width,height)=(number(&header[1])?,number(&header[2])?);
+        let mut luma=Vec::with_capacity(width*height);
+        if magic==b"P4" {
+            let mut row=vec![0u8;width.div_ceil(8)];
+            for _ in 0..height {
+                input.read_exact(&mut row)?;
+                luma.extend((0..width).map(|x|if row[x/8]&(0b10000000>>(x%8))!=0 {0} else {3}));
+            }
+        } else {
+            if number(&header[3])?!=255 {
+                return Err(invalid("expected an 8 bit PGM"));
+            }
+            let mut gray=vec![0u8;width*height];
+            input.read_exact(&mut gray)?;
+            luma.extend(gray.iter().map(|&g|map.luma(g)));
+        }
+        Ok(Snapshot {width,height,luma})
+    }
+
+    /// Compares two snapshots, returning `None` if they are identical
+    pub fn diff(&self,other:&Snapshot)->Option<SnapshotDiff> {
+        if (self.width,self.height)!=(other.width,other.height) {
+            return Some(SnapshotDiff {
+                size:((self.width,self.height),(other.width,other.height)),
+                pixels:0,
+                bounds:(0,0,0,0),
+                art:String::new(),
+            });
+        }
+        let mut pixels=0;
+        let (mut x0,mut y0,mut x1,mut y1)=(usize::MAX,usize::MAX,0,0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.luma(x,y)!=other.luma(x,y) {
+                    pixels+=1;
+                    x0=x0.min(x);
+                    y0=y0.min(y);
+                    x1=x1.max(x);
+                    y1=y1.max(y);
+                }
+            }
+        }
+        if pixels==0 {
+            return None;
+        }
+        // Draw the changed area (or the start of it) as text: `.` for identical pixels and the
+        // expected and actual luma for each changed one.
+        let mut art=String::new();
+        for y in y0..=y1.min(y0+DIFF_ART_ROWS-1) {
+            let _=write!(art,"{:4} ",y);
+            for x in x0..=x1.min(x0+DIFF_ART_COLUMNS-1) {
+                let (expected,actual)=(self.luma(x,y),other.luma(x,y));
+                if expected==actual {
+                    art.push_str(" . ");
+                } else {
+                    let _=write!(art,"{}>{}",expected,actual);
+                }
+            }
+            art.push('\n');
+        }
+        Some(SnapshotDiff {
+            size:((self.width,self.height),(other.width,other.height)),
+            pixels,
+            bounds:(x0,y0,x1,y1),
+            art,
+        })
+    }
+}
+
+const DIFF_ART_ROWS:usize=24;
+const DIFF_ART_COLUMNS:usize=32;
+
+/// Differences between two [`Snapshot`]s
+#[derive(Clone,Debug)]
+pub struct SnapshotDiff {
+    size:((usize,usize),(usize,usize)),
+    pixels:usize,
+    bounds:(usize,usize,usize,usize),
+    art:String,
+}
+impl SnapshotDiff {
+    /// Number of pixels that differ
+    pub fn pixels(&self)->usize {self.pixels}
+    /// Inclusive bounding box of the changed pixels as `(x0, y0, x1, y1)`
+    pub fn bounds(&self)->(usize,usize,usize,usize) {self.bounds}
+}
+impl core::fmt::Display for SnapshotDiff {
+    fn fmt(&self,f:&mut core::fmt::Formatter)->core::fmt::Result {
+        let ((ew,eh),(aw,ah))=self.size;
+        if (ew,eh)!=(aw,ah) {
+            return write!(f,"expected a {}x{} image, got {}x{}",ew,eh,aw,ah);
+        }
+        let (x0,y0,x1,y1)=self.bounds;
+        writeln!(f,"{} pixels differ between ({}, {}) and ({}, {})",self.pixels,x0,y0,x1,y1)?;
+        writeln!(f,"expected>actual luma, starting at column {}:",x0)?;
+        f.write_str(&self.art)
+    }
+}
+
+/// Compares the contents of `display` against the golden image at `path`.
+///
+/// If the golden image does not exist or the [`UPDATE_ENV`] environment variable is set, the
+/// golden image is (re)written instead. On a mismatch the actual image is saved next to the
+/// golden one with an `actual` extension prefix (`foo.png` -> `foo.actual.png`) and the test
+/// panics with a description of the difference. PBM golden images only compare black and white.
+pub fn assert_snapshot<D:Display,P:AsRef<Path>>(display:&D,path:P) {
+    let path=path.as_ref();
+    let map=GrayMap::default();
+    let mut actual=Snapshot::new(display);
+    if let Ok(Format::Pbm)=Format::of(path) {
+        actual=actual.to_binary();
+    }
+    if env::var_os(UPDATE_ENV).is_some() {
+        actual.save(path,map).unwrap_or_else(|e|panic!("failed to write {}: {}",path.display(),e));
+        return;
+    }
+    let expected=match Snapshot::load(path,map) {
+        Ok(expected)=>expected,
+        Err(e) if e.kind()==io::ErrorKind::NotFound=>{
+            let actual_path=actual_path(path);
+            let _=actual.save(&actual_path,map);
+            panic!(
+                "golden image {} does not exist, wrote {} (set {}=1 to accept it)",
+                path.display(),actual_path.display(),UPDATE_ENV,
+            );
+        },
+        Err(e)=>panic!("failed to read {}: {}",path.display(),e),
+    };
+    if let Some(diff)=expected.diff(&actual) {
+        let actual_path=actual_path(path);
+        let _=actual.save(&actual_path,map);
+        panic!("snapshot {} does not match (actual image saved to {}): {}",path.display(),actual_path.display(),diff);
+    }
+}
+
+fn actual_path(path:&Path)->PathBuf {
+    let mut name=path.file_stem().unwrap_or_default().to_os_string();
+    name.push(".actual");
+    if let Some(extension)=path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+enum Format {
+    Png,
+    Pgm,
+    Pbm,
+}
+impl Format {
+    fn of(path:&Path)->io::Result<Self> {
+        match path.extension().and_then(|e|e.to_str()) {
+            Some("png")=>Ok(Format::Png),
+            Some("pgm")=>Ok(Format::Pgm),
+            Some("pbm")=>Ok(Format::Pbm),
+            _=>Err(io::Error::new(io::ErrorKind::InvalidInput,"unsupported image extension, expected png, pgm or pbm")),
+        }
+    }
+}
+
+fn invalid(message:&str)->io::Error {
+    io::Error::new(io::ErrorKind::InvalidData,message)
+}
+
+/// Reads a whitespace separated PNM header token, skipping comments
+fn read_token<R:BufRead>(input:&mut R)->io::Result<String> {
+    let mut token=String::new();
+    let mut byte=[0u8];
+    loop {
+        input.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' if token.is_empty()=>{
+                let mut comment=Vec::new();
+                input.read_until(b'\n',&mut comment)?;
+            },
+            b if b.is_ascii_whitespace()=>{
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            },
+            b=>token.push(b as char),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Recording and decoding of the command stream sent to the controller
+//!
+//! Requires the `trace` feature. Wrap the SPI bus in a [`TraceSpi`] before handing it to
+//! [`Ssd1681`](crate::driver::Ssd1681) and it keeps the last `N` [`Event`]s in a ring buffer
+//! without allocating. The events can be printed directly with [`decode`], or serialized with
+//! [`Event::encode`] and decoded later on a host with [`Event::parse`].
+//!
+//! ```text
+//! SET_RAMXPOS start=0 end=24
+//! SET_RAMYPOS start=0 end=199
+//! WRITE_BUFFER1_DATA len=5000 [00 00 00 00 00 00 00 00 ..]
+//! ```
+
+use core::fmt;
+use embedded_hal::spi::{
+    ErrorType,
+    blocking::{Operation, Transactional, Write},
+};
+use crate::{cmd, config::Border, flag};
+
+/// Number of data bytes kept for each [`Event::Data`]
+pub const DATA_PREVIEW:usize=8;
+
+/// Something sent over the bus
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Event {
+    /// A command byte
+    Command(u8),
+    /// Data following a command. Consecutive writes are merged into one event.
+    Data {
+        /// Total number of bytes
+        len:u32,
+        /// The first bytes, see `preview_len`
+        preview:[u8;DATA_PREVIEW],
+    },
+}
+impl Event {
+    /// Size of the largest encoded event
+    pub const MAX_ENCODED_LEN:usize=6+DATA_PREVIEW;
+
+    /// Number of valid bytes in `preview` (0 for commands)
+    pub fn preview_len(&self)->usize {
+        match self {
+            Event::Command(_)=>0,
+            Event::Data {len,..}=>(*len as usize).min(DATA_PREVIEW),
+        }
+    }
+    /// Serializes the event into `out`, returning the number of bytes used.
+    ///
+    /// `out` must hold at least [`Event::MAX_ENCODED_LEN`] bytes.
+    pub fn encode(&self,out:&mut [u8])->usize {
+        match *self {
+            Event::Command(command)=>{
+                out[0]=0;
+                out[1]=command;
+                2
+            },
+            Event::Data {len,preview}=>{
+                let n=self.preview_len();
+                out[0]=1;
+                out[1..5].copy_from_slice(&len.to_le_bytes());
+                out[5]=n as u8;
+                out[6..6+n].copy_from_slice(&preview[..n]);
+                6+n
+            },
+        }
+    }
+    /// Reads an event written by [`Event::encode`], returning it and the number of bytes used
+    pub fn parse(bytes:&[u8])->Option<(Event,usize)> {
+        match *bytes.first()? {
+            0=>Some((Event::Command(*bytes.get(1)?),2)),
+            1=>{
+                let len=u32::from_le_bytes([*bytes.get(1)?,*bytes.get(2)?,*bytes.get(3)?,*bytes.get(4)?]);
+                let n=*bytes.get(5)? as usize;
+                let mut preview=[0;DATA_PREVIEW];
+                preview[..n.min(DATA_PREVIEW)].copy_from_slice(bytes.get(6..6+n.min(DATA_PREVIEW))?);
+                Some((Event::Data {len,preview},6+n))
+            },
+            _=>None,
+        }
+    }
+}
+
+/// An SPI bus wrapper that records the last `N` events sent through it
+pub struct TraceSpi<SPI,const N:usize> {
+    spi:SPI,
+    events:[Event;N],
+    next:usize,
+    len:usize,
+    dropped:u32,
+}
+impl<SPI,const N:usize> TraceSpi<SPI,N> {
+    /// Wraps `spi`
+    pub fn new(spi:SPI)->Self {
+        TraceSpi {
+            spi,
+            events:[Event::Command(0);N],
+            next:0,
+            len:0,
+            dropped:0,
+        }
+    }
+    /// Returns the wrapped bus
+    pub fn inner(&mut self)->&mut SPI {&mut self.spi}
+    /// Unwraps the bus, dropping the trace
+    pub fn into_inner(self)->SPI {self.spi}
+    /// Recorded events, oldest first
+    pub fn events(&self)->impl Iterator<Item=Event>+'_ {
+        let start=(self.next+N-self.len)%N.max(1);
+        (0..self.len).map(move |i|self.events[(start+i)%N])
+    }
+    /// Number of events that were overwritten because the buffer was full
+    pub fn dropped(&self)->u32 {self.dropped}
+    /// Forgets all recorded events
+    pub fn clear(&mut self) {
+        self.len=0;
+        self.dropped=0;
+    }
+    /// Serializes the recorded events into `out`, oldest first. Returns the number of bytes
+    /// written; events that do not fit are left out.
+    pub fn dump(&self,out:&mut [u8])->usize {
+        let mut written=0;
+        let mut scratch=[0;Event::MAX_ENCODED_LEN];
+        for event in self.events() {
+            let n=event.encode(&mut scratch);
+            if written+n>out.len() {
+                break;
+            }
+            out[written..written+n].copy_from_slice(&scratch[..n]);
+            written+=n;
+        }
+        written
+    }
+
+    fn last_mut(&mut self)->Option<&mut Event> {
+        if self.len==0 {
+            return None;
+        }
+        Some(&mut self.events[(self.next+N-1)%N])
+    }
+    fn push(&mut self,event:Event) {
+        if N==0 {
+            return;
+        }
+        self.events[self.next]=event;
+        self.next=(self.next+1)%N;
+        if self.len==N {
+            self.dropped=self.dropped.saturating_add(1);
+        } else {
+            self.len+=1;
+        }
+    }
+    fn record_command(&mut self,command:u8) {
+        self.push(Event::Command(command));
+    }
+    fn record_data(&mut self,data:&[u8]) {
+        if let Some(Event::Data {len,preview})=self.last_mut() {
+            let used=(*len as usize).min(DATA_PREVIEW);
+            let n=data.len().min(DATA_PREVIEW-used);
+            preview[used..used+n].copy_from_slice(&data[..n]);
+            *len=len.saturating_add(data.len() as u32);
+            return;
+        }
+        let mut preview=[0;DATA_PREVIEW];
+        let n=data.len().min(DATA_PREVIEW);
+        preview[..n].copy_from_slice(&data[..n]);
+        self.push(Event::Data {len:data.len() as u32,preview});
+    }
+}
+impl<SPI:ErrorType,const N:usize> ErrorType for TraceSpi<SPI,N> {
+    type Error=SPI::Error;
+}
+impl<SPI:Write,const N:usize> Write for TraceSpi<SPI,N> {
+    fn write(&mut self,words:&[u8])->Result<(),Self::Error> {
+        self.record_data(words);
+        self.spi.write(words)
+    }
+}
+impl<SPI:Transactional,const N:usize> Transactional for TraceSpi<SPI,N> {
+    fn exec<'a>(&mut self,operations:&mut [Operation<'a,u8>])->Result<(),Self::Error> {
+        // The driver sends commands as transfers and data as writes
+        for operation in operations.iter() {
+            match operation {
+                Operation::Transfer(_,words)=>words.iter().for_each(|&c|self.record_command(c)),
+                Operation::TransferInPlace(words)=>words.iter().for_each(|&c|self.record_command(c)),
+                Operation::Write(words)=>self.record_data(words),
+                Operation::Read(_)=>{},
+            }
+        }
+        self.spi.exec(operations)
+    }
+}
+
+/// Groups events into commands with their data
+pub fn decode<I:IntoIterator<Item=Event>>(events:I)->Decoder<I::IntoIter> {
+    Decoder {events:events.into_iter().peekable()}
+}
+
+/// Iterator returned by [`decode`]
+pub struct Decoder<I:Iterator<Item=Event>> {
+    events:core::iter::Peekable<I>,
+}
+impl<I:Iterator<Item=Event>> Iterator for Decoder<I> {
+    type Item=Decoded;
+    fn next(&mut self)->Option<Decoded> {
+        let command=match self.events.next()? {
+            Event::Command(command)=>Some(command),
+            // Data whose command was dropped from the ring buffer
+            Event::Data {len,preview}=>return Some(Decoded {command:None,len,preview}),
+        };
+        let (len,preview)=match self.events.peek() {
+            Some(&Event::Data {len,preview})=>{
+                self.events.next();
+                (len,preview)
+            },
+            _=>(0,[0;DATA_PREVIEW]),
+        };
+        Some(Decoded {command,len,preview})
+    }
+}
+
+/// A command and its data, printed as e.g. `SET_RAMXPOS start=0 end=24`
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct Decoded {
+    /// Command byte, `None` if the data's command is no longer in the trace
+    pub command:Option<u8>,
+    /// Number of data bytes
+    pub len:u32,
+    /// The first data bytes
+    pub preview:[u8;DATA_PREVIEW],
+}
+impl Decoded {
+    /// The data bytes that were recorded
+    pub fn data(&self)->&[u8] {
+        &self.preview[..(self.len as usize).min(DATA_PREVIEW)]
+    }
+    fn write_bytes(&self,f:&mut fmt::Formatter)->fmt::Result {
+        write!(f,"len={} [",self.len)?;
+        for (i,byte) in self.data().iter().enumerate() {
+            if i>0 {
+                f.write_str(" ")?;
+            }
+            write!(f,"{:02x}",byte)?;
+        }
+        if self.len as usize>DATA_PREVIEW {
+            f.write_str(" ..")?;
+        }
+        f.write_str("]")
+    }
+    fn write_flags(&self,f:&mut fmt::Formatter,names:&[(u8,&str)])->fmt::Result {
+        match self.data() {
+            [value]=>match names.iter().find(|(flag,_)|flag==value) {
+                Some((_,name))=>f.write_str(name),
+                None=>write!(f,"{:#04x}",value),
+            },
+            _=>self.write_bytes(f),
+        }
+    }
+}
+impl fmt::Display for Decoded {
+    fn fmt(&self,f:&mut fmt::Formatter)->fmt::Result {
+        let command=match self.command {
+            Some(command)=>command,
+            None=>{
+                f.write_str("<data> ")?;
+                return self.write_bytes(f);
+            },
+        };
+        match cmd::name(command) {
+            Some(name)=>f.write_str(name)?,
+            None=>write!(f,"{:#04x}",command)?,
+        }
+        if self.len==0 {
+            return Ok(());
+        }
+        f.write_str(" ")?;
+        let d=self.data();
+        match (command,d) {
+            (cmd::DRIVER_CONTROL,&[lo,hi,flags])=>write!(
+                f,"mux={} gd={} sm={} tb={}",
+                u16::from_le_bytes([lo,hi&1])+1,(flags>>2)&1,(flags>>1)&1,flags&1,
+            ),
+            (cmd::SET_RAMXPOS,&[start,end])=>write!(f,"start={} end={}",start,end),
+            (cmd::SET_RAMYPOS,&[s0,s1,e0,e1])=>write!(
+                f,"start={} end={}",
+                u16::from_le_bytes([s0,s1]),u16::from_le_bytes([e0,e1]),
+            ),
+            (cmd::SET_RAMX_COUNTER,&[x])=>write!(f,"x={}",x),
+            (cmd::SET_RAMY_COUNTER,&[y0,y1])=>write!(f,"y={}",u16::from_le_bytes([y0,y1])),
+            (cmd::DATA_ENTRY_MODE,&[mode])=>{
+                let names=[
+                    (flag::DATA_ENTRY_Y_FIRST,"DATA_ENTRY_Y_FIRST"),
+                    (flag::DATA_ENTRY_INCRY,"DATA_ENTRY_INCRY"),
+                    (flag::DATA_ENTRY_INCRX,"DATA_ENTRY_INCRX"),
+                ];
+                let mut first=true;
+                for &(bit,name) in names.iter() {
+                    if mode&bit!=0 {
+                        if !first {
+                            f.write_str("|")?;
+                        }
+                        first=false;
+                        f.write_str(name)?;
+                    }
+                }
+                if first {
+                    f.write_str("DATA_ENTRY_DECRY_DECRX")?;
+                }
+                Ok(())
+            },
+            (cmd::TEMP_CONTROL,_)=>self.write_flags(f,&[(flag::INTERNAL_TEMP_SENSOR,"INTERNAL_TEMP_SENSOR")]),
+            (cmd::BORDER_WAVEFORM_CONTROL,&[bits])=>match Border::from_bits(bits) {
+                Some(Border::Lut(n))=>write!(f,"BORDER_WAVEFORM_FOLLOW_LUT|BORDER_WAVEFORM_LUT{}",n),
+                Some(Border::LutVcomOnRed(n))=>write!(f,"BORDER_WAVEFORM_LUT{}",n),
+                Some(Border::White)=>f.write_str("BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSL"),
+                Some(Border::Black)=>f.write_str("BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSH1"),
+                Some(Border::Red)=>f.write_str("BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSH2"),
+                Some(Border::Vss)=>f.write_str("BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSS"),
+                Some(Border::Vcom)=>f.write_str("BORDER_WAVEFORM_VCOM"),
+                Some(Border::Floating)=>f.write_str("BORDER_WAVEFORM_HIZ"),
+                _=>write!(f,"{:#04x}",bits),
+            },
+            (cmd::UPDATE_DISPLAY_CTRL2,_)=>self.write_flags(f,&[
+                (flag::BW_DISPLAY_MODE_1,"BW_DISPLAY_MODE_1"),
+                (flag::GRAY4_DISPLAY_MODE_1,"GRAY4_DISPLAY_MODE_1"),
+            ]),
+            _=>self.write_bytes(f),
+        }
+    }
+}
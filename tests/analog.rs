@@ -0,0 +1,55 @@
+use epd_driver::analog::{GateVoltage, OutOfRange, Phase, PhaseDuration, SoftStart, Vcom, Vsh, Vsl};
+
+#[test]
+fn voltages_encode_like_the_datasheet() {
+    assert_eq!(GateVoltage::from_millivolts(10_000).unwrap().code(),0x03);
+    assert_eq!(GateVoltage::from_millivolts(20_000).unwrap().code(),0x17);
+    assert_eq!(Vsh::from_millivolts(2_400).unwrap().code(),0x8E);
+    assert_eq!(Vsh::from_millivolts(8_800).unwrap().code(),0xCE);
+    assert_eq!(Vsh::from_millivolts(9_000).unwrap().code(),0x23);
+    assert_eq!(Vsh::from_millivolts(15_000).unwrap().code(),0x41);
+    assert_eq!(Vsh::from_millivolts(17_000).unwrap().code(),0x4B);
+    assert_eq!(Vsl::from_millivolts(-9_000).unwrap().code(),0x1A);
+    assert_eq!(Vsl::from_millivolts(-15_000).unwrap().code(),0x32);
+    assert_eq!(Vsl::from_millivolts(-17_000).unwrap().code(),0x3A);
+    assert_eq!(Vcom::from_millivolts(-200).unwrap().code(),0x08);
+    assert_eq!(Vcom::from_millivolts(-800).unwrap().code(),0x20);
+    assert_eq!(Vcom::from_millivolts(-3_000).unwrap().code(),0x78);
+}
+
+#[test]
+fn voltages_round_trip_and_round_towards_zero() {
+    assert_eq!(Vsh::from_millivolts(12_345).unwrap().millivolts(),12_200);
+    assert_eq!(Vsh::from_millivolts(3_050).unwrap().millivolts(),3_000);
+    assert_eq!(Vsh::from_millivolts(8_850).unwrap().code(),0xCE);
+    assert_eq!(Vsh::from_millivolts(8_999).unwrap().millivolts(),8_800);
+    assert_eq!(Vsl::from_millivolts(-10_700).unwrap().millivolts(),-10_500);
+    assert_eq!(Vcom::from_millivolts(-1_050).unwrap().millivolts(),-1_000);
+    assert_eq!(GateVoltage::from_millivolts(19_999).unwrap().millivolts(),19_500);
+}
+
+#[test]
+fn voltages_out_of_range_are_rejected() {
+    assert_eq!(GateVoltage::from_millivolts(9_500),Err(OutOfRange {min:10_000,max:20_000}));
+    assert_eq!(Vsh::from_millivolts(2_000),Err(OutOfRange {min:2_400,max:17_000}));
+    assert_eq!(Vsh::from_millivolts(17_200),Err(OutOfRange {min:2_400,max:17_000}));
+    assert_eq!(Vsl::from_millivolts(-8_000),Err(OutOfRange {min:-17_000,max:-9_000}));
+    assert_eq!(Vcom::from_millivolts(0),Err(OutOfRange {min:-3_000,max:-200}));
+}
+
+#[test]
+fn soft_start_defaults_to_reset_values() {
+    assert_eq!(SoftStart::default().codes(),[0x8B,0x9C,0x96,0x0F]);
+}
+
+#[test]
+fn soft_start_phases_are_range_checked() {
+    assert_eq!(Phase::new(0,4),None);
+    assert_eq!(Phase::new(8,16),None);
+    let soft_start=SoftStart {
+        phases:[Phase::new(8,15).unwrap(),Phase::new(1,4).unwrap(),Phase::new(4,8).unwrap()],
+        durations:[PhaseDuration::Ms10,PhaseDuration::Ms20,PhaseDuration::Ms30],
+    };
+    assert_eq!(soft_start.phases[0].strength(),8);
+    assert_eq!(soft_start.codes(),[0xFF,0x84,0xB8,0b10_01_00]);
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use core::fmt::Debug;
+use embedded_hal::spi::blocking::{Transactional, Write};
+use epd_driver::{
+    config::Config,
+    controller::{self, Controller},
+    emulator::{self, Emulator},
+    prelude::*,
+};
+
+/// A driver on an emulated controller
+pub type Driver<C=controller::Ssd1681,SPI=emulator::Spi>=Ssd1681<SPI,emulator::Busy,emulator::Reset,emulator::Delay,C>;
+
+/// An emulator and a driver initialised on it with the default config
+pub fn setup()->(Emulator,emulator::Spi,Driver) {
+    setup_with(Config::default())
+}
+/// An emulator and a driver initialised on it with `config`
+pub fn setup_with(config:Config)->(Emulator,emulator::Spi,Driver) {
+    let emulator=Emulator::new();
+    let mut spi=emulator.spi();
+    let driver=init(&emulator,&mut spi,config);
+    (emulator,spi,driver)
+}
+/// An emulator and a driver for controller `C` initialised on it with the default config
+pub fn setup_controller<C:Controller>()->(Emulator,emulator::Spi,Driver<C>) {
+    setup_controller_with(Config::default())
+}
+/// An emulator and a driver for controller `C` initialised on it with `config`
+pub fn setup_controller_with<C:Controller>(config:Config)->(Emulator,emulator::Spi,Driver<C>) {
+    let emulator=Emulator::new();
+    let mut spi=emulator.spi();
+    let driver=Ssd1681::with_controller(&mut spi,emulator.busy(),emulator.rst(),emulator.delay(),config).unwrap();
+    (emulator,spi,driver)
+}
+/// Initialises a driver on `emulator` through `spi`, e.g. a wrapper around [`Emulator::spi`]
+pub fn init<SPI>(emulator:&Emulator,spi:&mut SPI,config:Config)->Driver<controller::Ssd1681,SPI>
+where
+    SPI:Write+Transactional,
+    SPI::Error:Debug,
+{
+    Ssd1681::new(spi,emulator.busy(),emulator.rst(),emulator.delay(),config).unwrap()
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    draw_target::DrawTarget,
+    primitives::Rectangle,
+    Pixel,
+};
+use epd_driver::{
+    color::{BLACK, WHITE},
+    diff::{diff, DoubleBuffered},
+    prelude::*,
+};
+use common::setup;
+
+#[test]
+fn diff_finds_bounding_rectangles_of_changed_rows() {
+    let mut previous=Display1in54::new();
+    previous.clear(WHITE).unwrap();
+    let mut current=Display1in54::new();
+    current.clear(WHITE).unwrap();
+    assert_eq!(diff(previous.buffers(),current.buffers(),200).count(),0);
+
+    current.draw_iter([
+        Pixel(Point::new(10,20),BLACK),
+        Pixel(Point::new(30,21),BLACK),
+        Pixel(Point::new(199,100),BLACK),
+        Pixel(Point::new(1,199),BLACK),
+    ]).unwrap();
+    let areas:Vec<_>=diff(previous.buffers(),current.buffers(),200).collect();
+    assert_eq!(areas,[
+        Rectangle::new(Point::new(8,20),Size::new(24,2)),
+        Rectangle::new(Point::new(192,100),Size::new(8,1)),
+        Rectangle::new(Point::new(0,199),Size::new(8,1)),
+    ]);
+}
+
+#[test]
+fn flush_uploads_only_changes() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=DoubleBuffered::new(Display1in54::new());
+    display.clear(WHITE).unwrap();
+    assert!(display.is_dirty());
+    assert_eq!(display.flush(&mut driver,&mut spi).unwrap(),1);
+    assert_eq!(emulator.refreshes(),1);
+    assert!(!display.is_dirty());
+    assert_eq!(display.flush(&mut driver,&mut spi).unwrap(),0);
+    assert_eq!(emulator.refreshes(),1);
+
+    display.fill_solid(&Rectangle::new(Point::new(20,40),Size::new(20,10)),BLACK).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(100,150),Size::new(3,3)),BLACK).unwrap();
+    let bytes=emulator.bytes_written();
+    assert_eq!(display.flush(&mut driver,&mut spi).unwrap(),2);
+    assert_eq!(emulator.refreshes(),2);
+    // 3 bytes by 10 rows and 1 byte by 3 rows for each RAM, plus the window setup
+    assert!(emulator.bytes_written()-bytes<200);
+    assert_eq!(emulator.bw_ram(),display.buffer1());
+    assert_eq!(emulator.red_ram(),display.buffer2());
+    assert_eq!(emulator.ram_window(),((0,24),(0,199)));
+}
+
+#[test]
+fn flush_honours_hardware_rotation() {
+    let (emulator,mut spi,mut driver)=setup();
+    driver.set_rotation(&mut spi,DisplayRotation::Rotate180).unwrap();
+    let mut display=DoubleBuffered::new(Display1in54::new());
+    display.clear(WHITE).unwrap();
+    display.flush(&mut driver,&mut spi).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(20,40),Size::new(20,10)),BLACK).unwrap();
+    display.flush(&mut driver,&mut spi).unwrap();
+
+    let panel=emulator.panel().unwrap();
+    for y in 0..200 {
+        for x in 0..200 {
+            let black=(20..40).contains(&(199-x))&&(40..50).contains(&(199-y));
+            assert_eq!(panel.luma(x,y),if black {0} else {3},"pixel ({},{})",x,y);
+        }
+    }
+}
@@ -0,0 +1,758 @@
+mod common;
+
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    draw_target::DrawTarget,
+    primitives::Rectangle,
+    Pixel,
+};
+use embedded_hal::delay::blocking::DelayUs;
+use epd_driver::{
+    analog::{GateVoltage, SoftStart, SourceVoltages, Supply, Vcom, VciLevel, Vsh, Vsl},
+    config::{AddressCounter, Border, Builder, Config, DataEntryMode, GateScan},
+    color::{self, BLACK, WHITE},
+    controller,
+    driver::{Error, LutType, Status, DEFAULT_BUSY_TIMEOUT_MS},
+    emulator::Emulator,
+    graphics::BUFFER_LEN,
+    prelude::*,
+};
+use common::{setup, setup_controller, setup_controller_with, setup_with};
+
+#[test]
+fn init_configures_controller() {
+    let (emulator,_spi,_driver)=setup();
+    assert_eq!(emulator.resets(),1);
+    assert_eq!(emulator.register(0x01),Some(vec![199,0,0]));
+    assert_eq!(emulator.register(0x3C),Some(vec![0x05]));
+    assert_eq!(emulator.register(0x18),Some(vec![0x80]));
+    assert_eq!(emulator.data_entry_mode(),0b011);
+    assert_eq!(emulator.ram_window(),((0,24),(0,199)));
+}
+
+#[test]
+fn update_frames_fills_both_rams() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    display.draw_iter([Pixel(Point::new(10,20),BLACK)]).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    assert_eq!(emulator.bw_ram(),display.buffer1());
+    assert_eq!(emulator.red_ram(),display.buffer2());
+}
+
+#[test]
+fn display_frame_shows_buffer() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    display.draw_iter([Pixel(Point::new(10,20),BLACK)]).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let panel=emulator.panel().unwrap();
+    assert_eq!(emulator.refreshes(),1);
+    assert_eq!(panel.luma(10,20),0);
+    assert_eq!(panel.luma(11,20),3);
+}
+
+#[test]
+fn otp_waveform_shows_set_black_white_bits_white() {
+    let (emulator,mut spi,mut driver)=setup();
+    driver.update_frame1(&mut spi,&[0xf0;BUFFER_LEN]).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let panel=emulator.panel().unwrap();
+    assert!(!panel.custom_lut());
+    assert_eq!((0..8).map(|x|panel.luma(x,0)).collect::<Vec<_>>(),[3,3,3,3,0,0,0,0]);
+    // the refresh loaded the OTP waveform into the LUT register
+    assert_eq!(emulator.register(0x32).unwrap()[..3],[0x80,0x48,0x40]);
+}
+
+#[test]
+fn waits_for_busy_until_the_timeout() {
+    let (emulator,mut spi,mut driver)=setup();
+    assert_eq!(driver.busy_timeout(),DEFAULT_BUSY_TIMEOUT_MS);
+    emulator.set_busy_polls(300);
+    let start=emulator.elapsed_us();
+    driver.display_frame(&mut spi).unwrap();
+    assert_eq!(emulator.elapsed_us()-start,30_000);
+
+    driver.set_busy_timeout(50);
+    emulator.set_busy_polls(u32::MAX);
+    let start=emulator.elapsed_us();
+    assert_eq!(driver.display_frame(&mut spi),Err(Error::BusyTimeout));
+    assert_eq!(emulator.elapsed_us()-start,50_000);
+}
+
+/// A delay that fails for waits shorter than `shortest_us`
+struct FailingDelay {
+    shortest_us:u32,
+}
+impl DelayUs for FailingDelay {
+    type Error=();
+    fn delay_us(&mut self,us:u32)->Result<(),()> {
+        if us<self.shortest_us {Err(())} else {Ok(())}
+    }
+}
+
+#[test]
+fn delay_errors_are_returned() {
+    let emulator=Emulator::new();
+    let mut spi=emulator.spi();
+    // fails during the reset
+    let driver=Ssd1681::new(&mut spi,emulator.busy(),emulator.rst(),FailingDelay {shortest_us:u32::MAX},Config::default());
+    assert_eq!(driver.err(),Some(Error::Delay));
+    // resets, but fails between two BUSY polls
+    emulator.set_busy_polls(3);
+    let driver=Ssd1681::new(&mut spi,emulator.busy(),emulator.rst(),FailingDelay {shortest_us:1_000},Config::default());
+    assert_eq!(driver.err(),Some(Error::Delay));
+    assert_eq!(emulator.resets(),1);
+}
+
+#[test]
+fn clear_frames_makes_panel_white() {
+    let (emulator,mut spi,mut driver)=setup();
+    driver.clear_frames(&mut spi).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let panel=emulator.panel().unwrap();
+    assert!((0..200).all(|y|(0..200).all(|x|panel.luma(x,y)==3)));
+}
+
+#[test]
+fn window_limits_ram_writes() {
+    let (emulator,mut spi,mut driver)=setup();
+    driver.clear_frames(&mut spi).unwrap();
+    driver.set_window(&mut spi,Rectangle::new(Point::new(8,4),Size::new(16,2))).unwrap();
+    assert_eq!(emulator.ram_counter(),(1,4));
+    assert_eq!(emulator.ram_window(),((1,2),(4,5)));
+    driver.unset_window(&mut spi).unwrap();
+    assert_eq!(emulator.ram_window(),((0,24),(0,199)));
+}
+
+#[test]
+fn windows_are_clipped_and_aligned() {
+    let (emulator,mut spi,mut driver)=setup();
+    // a single pixel takes the whole byte
+    driver.set_window(&mut spi,Rectangle::new(Point::new(3,5),Size::new(1,1))).unwrap();
+    assert_eq!(driver.current_window(),Some(Rectangle::new(Point::new(0,5),Size::new(8,1))));
+    assert_eq!(emulator.ram_window(),((0,0),(5,5)));
+    assert_eq!(emulator.ram_counter(),(0,5));
+
+    driver.set_window(&mut spi,Rectangle::new(Point::new(190,195),Size::new(20,20))).unwrap();
+    assert_eq!(driver.current_window(),Some(Rectangle::new(Point::new(184,195),Size::new(16,5))));
+    assert_eq!(emulator.ram_window(),((23,24),(195,199)));
+
+    driver.set_window(&mut spi,Rectangle::new(Point::new(-10,-10),Size::new(20,11))).unwrap();
+    assert_eq!(driver.current_window(),Some(Rectangle::new(Point::new(0,0),Size::new(16,1))));
+    assert_eq!(emulator.ram_window(),((0,1),(0,0)));
+}
+
+#[test]
+fn logical_rectangles_map_to_panel_coordinates() {
+    let panel=Size::new(200,200);
+    let area=Rectangle::new(Point::new(10,20),Size::new(30,40));
+    assert_eq!(DisplayRotation::Rotate0.to_panel(&area,panel),area);
+    assert_eq!(DisplayRotation::Rotate90.to_panel(&area,panel),Rectangle::new(Point::new(140,10),Size::new(40,30)));
+    assert_eq!(DisplayRotation::Rotate180.to_panel(&area,panel),Rectangle::new(Point::new(160,140),Size::new(30,40)));
+    assert_eq!(DisplayRotation::Rotate270.to_panel(&area,panel),Rectangle::new(Point::new(20,160),Size::new(40,30)));
+    // every corner pixel lands on the matching corner of the panel
+    let corner=Rectangle::new(Point::zero(),Size::new(1,1));
+    assert_eq!(DisplayRotation::Rotate90.to_panel(&corner,panel).top_left,Point::new(199,0));
+    assert_eq!(DisplayRotation::Rotate180.to_panel(&corner,panel).top_left,Point::new(199,199));
+    assert_eq!(DisplayRotation::Rotate270.to_panel(&corner,panel).top_left,Point::new(0,199));
+}
+
+#[test]
+fn logical_windows_follow_display_rotation() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    display.set_rotation(DisplayRotation::Rotate180);
+    let window=Rectangle::new(Point::new(0,0),Size::new(16,8));
+    driver.set_logical_window(&mut spi,&display,window).unwrap();
+    assert_eq!(driver.current_window(),Some(Rectangle::new(Point::new(184,192),Size::new(16,8))));
+    assert_eq!(emulator.ram_window(),((23,24),(192,199)));
+
+    // only the rows of the logical area reach the RAM
+    driver.update_frames(&mut spi,&display).unwrap();
+    let area=Rectangle::new(Point::new(20,30),Size::new(10,5));
+    display.fill_solid(&area,BLACK).unwrap();
+    driver.update_logical_area(&mut spi,&display,area).unwrap();
+    assert_eq!(emulator.bw_ram(),display.buffer1());
+    assert_eq!(emulator.red_ram(),display.buffer2());
+    assert_eq!(emulator.ram_window(),((23,24),(192,199)));
+}
+
+#[test]
+fn invalid_windows_are_rejected() {
+    let (emulator,mut spi,mut driver)=setup();
+    let window=Rectangle::new(Point::new(8,8),Size::new(8,8));
+    driver.set_window(&mut spi,window).unwrap();
+
+    let empty=Rectangle::new(Point::new(8,4),Size::new(0,3));
+    assert_eq!(driver.set_window(&mut spi,empty),Err(Error::EmptyWindow(empty)));
+    for outside in [
+        Rectangle::new(Point::new(-20,0),Size::new(10,10)),
+        Rectangle::new(Point::new(200,0),Size::new(8,8)),
+        Rectangle::new(Point::new(0,200),Size::new(8,1)),
+    ] {
+        assert_eq!(driver.set_window(&mut spi,outside),Err(Error::WindowOutsidePanel(outside)));
+    }
+    assert_eq!(driver.current_window(),Some(window));
+    assert_eq!(emulator.ram_window(),((1,1),(8,15)));
+}
+
+#[test]
+fn set_lut_loads_waveform() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    driver.set_lut(&mut spi,LutType::Partial,&mut display).unwrap();
+    assert_eq!(emulator.register(0x32).map(|lut|lut.len()),Some(153));
+    assert_eq!(emulator.register(0x2C),Some(vec![0x28]));
+    assert_eq!(driver.current_lut_type(),LutType::Partial);
+}
+
+#[test]
+fn other_controllers_use_their_geometry() {
+    let (emulator,mut spi,mut driver)=setup_controller::<controller::Ssd1680>();
+    assert_eq!(emulator.register(0x01),Some(vec![0x27,0x01,0]));
+    assert_eq!(emulator.ram_window(),((0,21),(0,295)));
+    let mut display=Display1in54::new();
+    let buffers=(display.buffer1().to_vec(),display.buffer2().to_vec());
+    for &lut_type in [LutType::Gray4,LutType::Fast,LutType::Differential,LutType::Partial].iter() {
+        assert!(matches!(driver.set_lut(&mut spi,lut_type,&mut display),Err(Error::CustomLutUnsupported)),"{:?}",lut_type);
+        assert_eq!(driver.current_lut_type(),LutType::Full);
+        assert!(!display.is_inverted());
+        assert_eq!((display.buffer1().to_vec(),display.buffer2().to_vec()),buffers);
+    }
+    assert!(matches!(driver.display_gray4(&mut spi,&display),Err(Error::CustomLutUnsupported)));
+    driver.set_lut(&mut spi,LutType::Full,&mut display).unwrap();
+    assert_eq!(emulator.register(0x32),None);
+    driver.display_frame(&mut spi).unwrap();
+    assert_eq!(emulator.panel().unwrap().update_control(),0xF7);
+}
+
+#[test]
+fn panel_size_sets_gates_and_ram_windows() {
+    let config=Builder::new().panel_size(122,250).build();
+    let (emulator,mut spi,mut driver)=setup_controller_with::<controller::Ssd1680>(config);
+    assert_eq!(driver.panel_size(),Size::new(122,250));
+    assert_eq!(emulator.register(0x01),Some(vec![249,0,0]));
+    assert_eq!(emulator.ram_window(),((0,15),(0,249)));
+    driver.update_frames(&mut spi,&PanelDisplay::new(122,250)).unwrap();
+    assert_eq!(emulator.ram_window(),((0,15),(0,249)));
+    assert_eq!(driver.set_rotation(&mut spi,DisplayRotation::Rotate180),Err(Error::UnsupportedRotation(DisplayRotation::Rotate180)));
+
+    let emulator=Emulator::new();
+    let mut spi=emulator.spi();
+    let config=Builder::new().panel_size(200,250).build();
+    let driver=Ssd1681::<_,_,_,_,controller::Ssd1680>::with_controller(&mut spi,emulator.busy(),emulator.rst(),emulator.delay(),config);
+    assert!(matches!(driver,Err(Error::PanelSize(size)) if size==Size::new(200,250)));
+}
+
+#[test]
+fn panel_display_uploads_into_the_panel_area() {
+    let (emulator,mut spi,mut driver)=setup_with(Builder::new().panel_size(120,100).build());
+    assert_eq!(emulator.register(0x01),Some(vec![99,0,0]));
+    let mut display=PanelDisplay::new(120,100);
+    display.draw_iter([Pixel(Point::new(0,0),BLACK),Pixel(Point::new(119,99),BLACK)]).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    let (before,ram)=(Emulator::new().bw_ram(),emulator.bw_ram());
+    assert_eq!((ram[0],ram[99*25+14]),(0x7f,0xfe));
+    // the RAM outside of the panel is left alone
+    assert_eq!((ram[99*25+15],ram[100*25]),(before[99*25+15],before[100*25]));
+}
+
+#[test]
+fn buffers_of_another_size_are_rejected() {
+    let (emulator,mut spi,mut driver)=setup_controller::<controller::Ssd1680>();
+    let display=Display1in54::new();
+    emulator.clear_commands();
+    assert_eq!(driver.update_frames(&mut spi,&display),Err(Error::BufferSize(22*296)));
+    assert_eq!(driver.update_area(&mut spi,&display,Rectangle::new(Point::zero(),Size::new(8,8))),Err(Error::BufferSize(22*296)));
+    assert_eq!(driver.update_frame2(&mut spi,&[0xff;22*296+1]),Err(Error::BufferSize(22*296)));
+    assert_eq!(emulator.commands().len(),0);
+    driver.update_frames(&mut spi,&PanelDisplay::new(176,296)).unwrap();
+}
+
+fn gray_bands()->Display1in54 {
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    for (i,&color) in [color::LGRAY,color::DGRAY,BLACK].iter().enumerate() {
+        let top=50*(i as i32+1);
+        display.fill_solid(&Rectangle::new(Point::new(0,top),Size::new(200,50)),color).unwrap();
+    }
+    display
+}
+
+fn ram_and_lut_commands(emulator:&Emulator)->Vec<u8> {
+    emulator.commands().into_iter().map(|c|c.command).filter(|c|[0x20,0x24,0x26,0x32].contains(c)).collect()
+}
+
+#[test]
+fn display_gray4_shows_four_grays() {
+    let (emulator,mut spi,mut driver)=setup();
+    let display=gray_bands();
+    let window=Rectangle::new(Point::new(8,8),Size::new(8,8));
+    driver.set_window(&mut spi,window).unwrap();
+    let window_ram=emulator.ram_window();
+    emulator.clear_commands();
+
+    driver.display_gray4(&mut spi,&display).unwrap();
+    assert_eq!(ram_and_lut_commands(&emulator),[0x24,0x26,0x32,0x20,0x32]);
+    let panel=emulator.panel().unwrap();
+    assert!(panel.custom_lut());
+    for (y,luma) in [(25,3),(75,2),(125,1),(175,0)] {
+        assert!((1..200).all(|x|panel.luma(x,y)==luma),"row {}",y);
+    }
+    assert_eq!(driver.current_lut_type(),LutType::Full);
+    assert_eq!(emulator.ram_window(),window_ram);
+    // the display itself is left alone
+    assert!(!display.is_inverted());
+}
+
+#[test]
+fn display_gray4_accepts_inverted_displays() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=gray_bands();
+    driver.set_lut(&mut spi,LutType::Gray4,&mut display).unwrap();
+    emulator.clear_commands();
+
+    driver.display_gray4(&mut spi,&display).unwrap();
+    assert_eq!(ram_and_lut_commands(&emulator),[0x32,0x24,0x26,0x32,0x20]);
+    let panel=emulator.panel().unwrap();
+    for (y,luma) in [(25,3),(75,2),(125,1),(175,0)] {
+        assert_eq!(panel.luma(100,y),luma);
+    }
+    assert_eq!(driver.current_lut_type(),LutType::Gray4);
+}
+
+#[test]
+fn fast_lut_refreshes_black_and_white() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    driver.set_lut(&mut spi,LutType::Fast,&mut display).unwrap();
+    assert!(!display.is_inverted());
+    let lut=emulator.register(0x32).unwrap();
+    assert_eq!((lut[0],lut[12],lut[60]),(0x40,0x80,0x0A));
+
+    display.clear(WHITE).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(20,20),Size::new(10,10)),BLACK).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let panel=emulator.panel().unwrap();
+    assert!(panel.custom_lut());
+    assert_eq!((panel.luma(25,25),panel.luma(50,50)),(0,3));
+    assert_eq!(driver.fast_refreshes(),1);
+}
+
+#[test]
+fn fast_refreshes_are_followed_by_a_full_one() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    driver.set_lut(&mut spi,LutType::Fast,&mut display).unwrap();
+    driver.set_full_refresh_interval(3);
+    let fast=emulator.register(0x32);
+    let mut custom=Vec::new();
+    for i in 0..8 {
+        if i%2==0 {
+            driver.display_frame(&mut spi).unwrap();
+        } else {
+            driver.display_window(&mut spi).unwrap();
+        }
+        custom.push(emulator.panel().unwrap().custom_lut());
+        // the forced full refresh loads the OTP waveform, the driver loads the fast one again
+        assert_eq!(emulator.register(0x32),fast,"refresh {}",i);
+    }
+    assert_eq!(custom,[true,true,true,false,true,true,true,false]);
+    assert_eq!(driver.fast_refreshes(),0);
+    assert_eq!(driver.current_lut_type(),LutType::Fast);
+
+    // a full refresh in between starts counting again
+    driver.display_frame(&mut spi).unwrap();
+    driver.set_lut(&mut spi,LutType::Full,&mut display).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    assert_eq!(driver.fast_refreshes(),0);
+
+    driver.set_lut(&mut spi,LutType::Fast,&mut display).unwrap();
+    driver.set_full_refresh_interval(0);
+    for _ in 0..20 {
+        driver.display_frame(&mut spi).unwrap();
+        assert!(emulator.panel().unwrap().custom_lut());
+    }
+}
+
+#[test]
+fn differential_refresh_keeps_previous_image_in_red_ram() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(16,16),Size::new(16,16)),BLACK).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let previous=display.buffer1().to_vec();
+
+    driver.set_lut(&mut spi,LutType::Differential,&mut display).unwrap();
+    display.clear(WHITE).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(48,48),Size::new(16,16)),BLACK).unwrap();
+    driver.display_differential(&mut spi,&display).unwrap();
+
+    let panel=emulator.panel().unwrap();
+    assert!(panel.custom_lut());
+    assert_eq!(panel.red_ram(),&previous[..]);
+    assert_eq!(panel.bw_ram(),display.buffer1());
+    assert_eq!((panel.luma(20,20),panel.luma(50,50)),(3,0));
+    // only the pixels that changed are driven, the others keep their state
+    for y in 0..200 {
+        for x in 0..200 {
+            let changed=matches!(panel.lut_index(x,y),1|2);
+            let expected=(16..32).contains(&x)&&(16..32).contains(&y)||(48..64).contains(&x)&&(48..64).contains(&y);
+            assert_eq!(changed,expected,"pixel ({},{})",x,y);
+            assert_eq!(panel.driven_frames(x,y)>0,changed,"pixel ({},{})",x,y);
+        }
+    }
+    assert_eq!(emulator.red_ram(),display.buffer1());
+    assert_eq!(emulator.bw_ram(),display.buffer1());
+}
+
+#[test]
+fn differential_needs_its_lut_and_normal_polarity() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    emulator.clear_commands();
+    assert_eq!(driver.display_differential(&mut spi,&display),Err(Error::LutNotLoaded(LutType::Differential)));
+    driver.set_lut(&mut spi,LutType::Fast,&mut display).unwrap();
+    assert_eq!(driver.display_differential(&mut spi,&display),Err(Error::LutNotLoaded(LutType::Differential)));
+    driver.set_lut(&mut spi,LutType::Differential,&mut display).unwrap();
+    display.invert_display();
+    emulator.clear_commands();
+    assert_eq!(driver.display_differential(&mut spi,&display),Err(Error::InvertedDisplay));
+    assert_eq!((emulator.refreshes(),emulator.commands().len()),(0,0));
+}
+
+#[test]
+fn differential_area_writes_only_the_area() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    driver.set_lut(&mut spi,LutType::Differential,&mut display).unwrap();
+    let window=Rectangle::new(Point::new(8,8),Size::new(8,8));
+    driver.set_window(&mut spi,window).unwrap();
+    let window_ram=emulator.ram_window();
+
+    let area=Rectangle::new(Point::new(100,10),Size::new(12,4));
+    display.fill_solid(&area,BLACK).unwrap();
+    emulator.clear_commands();
+    driver.display_differential_area(&mut spi,&display,area).unwrap();
+    assert_eq!(emulator.refreshes(),1);
+    let written:Vec<_>=emulator.commands().into_iter().filter(|c|c.command==0x24||c.command==0x26).map(|c|c.data.len()).collect();
+    assert_eq!(written,[2*4,2*4]);
+    assert_eq!(emulator.red_ram(),display.buffer1());
+    assert_eq!(emulator.bw_ram(),display.buffer1());
+    assert_eq!(emulator.ram_window(),window_ram);
+
+    driver.display_differential_area(&mut spi,&display,Rectangle::new(Point::new(300,0),Size::new(8,8))).unwrap();
+    assert_eq!(emulator.refreshes(),1);
+}
+
+#[test]
+fn config_is_applied_during_init() {
+    let config=Builder::new()
+        .gate_scan(GateScan {reverse:true,..GateScan::default()})
+        .data_entry(DataEntryMode {x_increment:false,y_increment:false,counter:AddressCounter::XFirst})
+        .border(Border::Floating)
+        .build();
+    let (emulator,mut spi,mut driver)=setup_with(config);
+    assert_eq!(emulator.register(0x01),Some(vec![199,0,0b001]));
+    assert_eq!(emulator.register(0x3C),Some(vec![0xC0]));
+    assert_eq!(emulator.data_entry_mode(),0b000);
+    assert_eq!(emulator.ram_window(),((24,0),(199,0)));
+    assert_eq!(emulator.ram_counter(),(24,199));
+
+    // the first byte written ends up mirrored in the bottom right corner
+    let mut buffer=vec![0;5000];
+    buffer[0]=0xA0;
+    driver.update_frame1(&mut spi,&buffer).unwrap();
+    assert_eq!(emulator.bw_ram()[4999],0x05);
+    assert_eq!(emulator.bw_ram()[0],0);
+}
+
+#[test]
+fn decrementing_x_mirrors_whole_pixels() {
+    let mode=DataEntryMode {x_increment:false,..DataEntryMode::default()};
+    let (emulator,mut spi,mut driver)=setup_with(Builder::new().data_entry(mode).build());
+    let mut display=Display1in54::new();
+    display.draw_iter([Pixel(Point::new(1,1),BLACK),Pixel(Point::new(17,3),BLACK)]).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    let area=Rectangle::new(Point::new(40,10),Size::new(8,2));
+    display.draw_iter([Pixel(Point::new(42,10),BLACK)]).unwrap();
+    driver.update_area(&mut spi,&display,area).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let mirrored=[(198,1),(182,3),(157,10)];
+    let panel=emulator.panel().unwrap();
+    for y in 0..200 {
+        for x in 0..200 {
+            assert_eq!(panel.luma(x,y),if mirrored.contains(&(x,y)) {0} else {3},"pixel ({},{})",x,y);
+        }
+    }
+
+    // a 180° rotation counts X up again, which leaves the image flipped vertically
+    driver.set_rotation(&mut spi,DisplayRotation::Rotate180).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+    let flipped=[(1,198),(17,196),(42,189)];
+    let panel=emulator.panel().unwrap();
+    for y in 0..200 {
+        for x in 0..200 {
+            assert_eq!(panel.luma(x,y),if flipped.contains(&(x,y)) {0} else {3},"pixel ({},{})",x,y);
+        }
+    }
+
+    let config=Builder::new().data_entry(mode).panel_size(122,250).build();
+    let emulator=Emulator::new();
+    let mut spi=emulator.spi();
+    let driver=Ssd1681::<_,_,_,_,controller::Ssd1680>::with_controller(&mut spi,emulator.busy(),emulator.rst(),emulator.delay(),config);
+    assert!(matches!(driver,Err(Error::UnsupportedDataEntry(rejected)) if rejected==mode));
+}
+
+#[test]
+fn column_first_data_entry_is_rejected() {
+    let emulator=Emulator::new();
+    let mut spi=emulator.spi();
+    let mode=DataEntryMode {counter:AddressCounter::YFirst,..DataEntryMode::default()};
+    let config=Builder::new().data_entry(mode).build();
+    let driver=Ssd1681::new(&mut spi,emulator.busy(),emulator.rst(),emulator.delay(),config);
+    assert!(matches!(driver,Err(Error::UnsupportedDataEntry(rejected)) if rejected==mode));
+    assert_eq!(emulator.resets(),0);
+}
+
+#[test]
+fn hardware_rotation_mirrors_ram() {
+    let (emulator,mut spi,mut driver)=setup();
+    driver.set_rotation(&mut spi,DisplayRotation::Rotate180).unwrap();
+    assert_eq!(emulator.data_entry_mode(),0b000);
+    assert_eq!(emulator.ram_window(),((24,0),(199,0)));
+
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    display.draw_iter([Pixel(Point::new(1,1),BLACK),Pixel(Point::new(17,3),BLACK)]).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+    driver.display_frame(&mut spi).unwrap();
+
+    let panel=emulator.panel().unwrap();
+    for y in 0..200 {
+        for x in 0..200 {
+            let black=(x,y)==(198,198)||(x,y)==(182,196);
+            assert_eq!(panel.luma(x,y),if black {0} else {3},"pixel ({},{})",x,y);
+        }
+    }
+}
+
+#[test]
+fn hardware_rotation_rejects_quarter_turns() {
+    let (emulator,mut spi,mut driver)=setup();
+    assert_eq!(driver.set_rotation(&mut spi,DisplayRotation::Rotate90),Err(Error::UnsupportedRotation(DisplayRotation::Rotate90)));
+    assert_eq!(driver.rotation(),DisplayRotation::Rotate0);
+    assert_eq!(emulator.data_entry_mode(),0b011);
+}
+
+#[test]
+fn set_border_updates_register() {
+    let (emulator,mut spi,mut driver)=setup();
+    for (border,bits) in [
+        (Border::Black,0x50),
+        (Border::White,0x60),
+        (Border::Red,0x70),
+        (Border::Vss,0x40),
+        (Border::Vcom,0x80),
+        (Border::Floating,0xC0),
+        (Border::Lut(3),0x07),
+        (Border::LutVcomOnRed(2),0x02),
+    ] {
+        driver.set_border(&mut spi,border).unwrap();
+        assert_eq!(emulator.register(0x3C),Some(vec![bits]));
+        assert_eq!(driver.config().border(),border);
+    }
+}
+
+#[test]
+fn analog_settings_survive_lut_changes() {
+    let (emulator,mut spi,mut driver)=setup();
+    let source=SourceVoltages {
+        vsh1:Vsh::from_millivolts(14_000).unwrap(),
+        vsh2:Vsh::from_millivolts(5_000).unwrap(),
+        vsl:Vsl::from_millivolts(-14_000).unwrap(),
+    };
+    driver.set_gate_voltage(&mut spi,GateVoltage::from_millivolts(18_000).unwrap()).unwrap();
+    driver.set_source_voltages(&mut spi,source).unwrap();
+    driver.set_vcom(&mut spi,Vcom::from_millivolts(-1_500).unwrap()).unwrap();
+    driver.set_soft_start(&mut spi,SoftStart::default()).unwrap();
+    assert_eq!(emulator.register(0x0C),Some(vec![0x8B,0x9C,0x96,0x0F]));
+
+    let mut display=Display1in54::new();
+    driver.set_lut(&mut spi,LutType::Partial,&mut display).unwrap();
+    assert_eq!(emulator.register(0x03),Some(vec![0x13]));
+    assert_eq!(emulator.register(0x04),Some(vec![0x3C,0xA8,0x2E]));
+    assert_eq!(emulator.register(0x2C),Some(vec![0x3C]));
+}
+
+#[test]
+fn user_id_round_trips() {
+    let (emulator,mut spi,mut driver)=setup();
+    let id=[1,2,3,4,5,6,7,8,9,10];
+    driver.write_user_id(&mut spi,&id).unwrap();
+    assert_eq!(emulator.register(0x38),Some(id.to_vec()));
+    assert_eq!(driver.read_user_id(&mut spi).unwrap(),id);
+}
+
+#[test]
+fn read_status_decodes_bits() {
+    let (emulator,mut spi,mut driver)=setup();
+    assert_eq!(driver.read_status(&mut spi).unwrap(),Status {hv_ready:true,vci_low:false,busy:false,chip_id:0});
+    emulator.set_status(false,true,2);
+    assert_eq!(driver.read_status(&mut spi).unwrap(),Status {hv_ready:false,vci_low:true,busy:false,chip_id:2});
+}
+
+#[test]
+fn check_supply_compares_vci_with_threshold() {
+    let (emulator,mut spi,mut driver)=setup();
+    emulator.set_vci(2_350);
+    assert!(driver.check_supply(&mut spi,VciLevel::V2_2).unwrap().refresh_safe());
+    assert_eq!(emulator.register(0x15),Some(vec![0x03]));
+    let supply=driver.check_supply(&mut spi,VciLevel::V2_4).unwrap();
+    assert_eq!(supply,Supply {hv_ready:true,vci_ok:false});
+    assert!(!supply.refresh_safe());
+    assert_eq!(emulator.register(0x22),Some(vec![0x03]));
+
+    emulator.set_vci(3_300);
+    emulator.set_status(false,false,0);
+    assert_eq!(driver.check_supply(&mut spi,VciLevel::V2_6).unwrap(),Supply {hv_ready:false,vci_ok:true});
+}
+
+#[test]
+fn deep_clean_cycles_black_and_white() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    driver.set_lut(&mut spi,LutType::Partial,&mut display).unwrap();
+    let window=Rectangle::new(Point::new(8,8),Size::new(16,16));
+    driver.set_window(&mut spi,window).unwrap();
+    let window_ram=emulator.ram_window();
+
+    driver.deep_clean(&mut spi,2).unwrap();
+    assert_eq!(emulator.refreshes(),4);
+    let panel=emulator.panel().unwrap();
+    assert!(panel.bw_ram().iter().chain(panel.red_ram()).all(|&byte|byte==0xff));
+    assert!((0..200).all(|y|(0..200).all(|x|panel.luma(x,y)==3)));
+
+    let luts:Vec<_>=emulator.commands().into_iter().filter(|c|c.command==0x32).map(|c|c.data[1]).collect();
+    assert_eq!(luts,[0x40,0x48,0x40]);    // partial, full, partial
+    assert_eq!(driver.current_lut_type(),LutType::Partial);
+    assert_eq!(driver.current_window(),Some(window));
+    assert_eq!(emulator.ram_window(),window_ram);
+}
+
+#[test]
+fn fills_stream_in_large_transactions() {
+    let (emulator,mut spi,mut driver)=setup();
+    emulator.clear_commands();
+    let (transactions,bytes)=(emulator.transactions(),emulator.bytes_written());
+    driver.clear_frame1(&mut spi).unwrap();
+    let transactions=emulator.transactions()-transactions;
+    assert_eq!(emulator.bw_ram(),vec![0xff;5000]);
+    // 40 writes of 128 bytes plus the window, counter and command setup
+    assert!((40..=50).contains(&transactions),"{} transactions",transactions);
+    assert_eq!(emulator.largest_write(),128);
+    assert!(emulator.bytes_written()-bytes<5000+50);
+
+    driver.set_rotation(&mut spi,DisplayRotation::Rotate180).unwrap();
+    let display=Display1in54::new();
+    emulator.clear_commands();
+    let transactions=emulator.transactions();
+    driver.update_frame1(&mut spi,display.buffer1()).unwrap();
+    assert!(emulator.transactions()-transactions<=50);
+    assert!(emulator.largest_write()<=128);
+}
+
+fn scene<D:DrawTarget<Color=Color>>(target:&mut D) where D::Error:core::fmt::Debug {
+    target.clear(WHITE).unwrap();
+    target.fill_solid(&Rectangle::new(Point::new(3,5),Size::new(60,41)),BLACK).unwrap();
+    target.fill_solid(&Rectangle::new(Point::new(50,30),Size::new(100,90)),color::LGRAY).unwrap();
+    target.draw_iter((1..199).map(|i|Pixel(Point::new(i,i),BLACK))).unwrap();
+}
+
+#[test]
+fn banded_rendering_matches_display() {
+    for (rotation,rows) in [(DisplayRotation::Rotate0,8),(DisplayRotation::Rotate90,3),(DisplayRotation::Rotate180,7),(DisplayRotation::Rotate270,8),(DisplayRotation::Rotate0,1)] {
+        let (emulator,mut spi,mut driver)=setup();
+        let mut display=Display1in54::new();
+        display.set_rotation(rotation);
+        scene(&mut display);
+        driver.update_frames(&mut spi,&display).unwrap();
+        let expected=(emulator.bw_ram(),emulator.red_ram());
+
+        let (emulator,mut spi,mut driver)=setup();
+        let mut strip=([0;200],[0;200]);
+        let strip=(&mut strip.0[..rows*25],&mut strip.1[..rows*25]);
+        let mut bands=0;
+        driver.draw_banded(&mut spi,strip,rotation,|band|{
+            bands+=1;
+            scene(band);
+        }).unwrap();
+        assert_eq!(bands,200usize.div_ceil(rows));
+        assert_eq!((emulator.bw_ram(),emulator.red_ram()),expected,"{:?} with {} rows",rotation,rows);
+        assert_eq!(emulator.ram_window(),((0,24),(0,199)));
+    }
+}
+
+#[test]
+fn band_and_display_draw_the_panel_corners() {
+    let corners=[Point::new(0,0),Point::new(199,0),Point::new(0,199),Point::new(199,199)];
+    let rotations=[DisplayRotation::Rotate0,DisplayRotation::Rotate90,DisplayRotation::Rotate180,DisplayRotation::Rotate270];
+    for &rotation in rotations.iter() {
+        for &corner in corners.iter() {
+            let (emulator,mut spi,mut driver)=setup();
+            let mut display=Display1in54::new();
+            display.set_rotation(rotation);
+            display.clear(WHITE).unwrap();
+            display.draw_iter([Pixel(corner,BLACK)]).unwrap();
+            assert_eq!(display.buffer1().iter().filter(|&&byte|byte!=0xff).count(),1,"{:?} {:?}",rotation,corner);
+            driver.update_frames(&mut spi,&display).unwrap();
+            let expected=(emulator.bw_ram(),emulator.red_ram());
+
+            let (emulator,mut spi,mut driver)=setup();
+            let mut strip=([0;100],[0;100]);
+            driver.draw_banded(&mut spi,(&mut strip.0[..],&mut strip.1[..]),rotation,|band|{
+                band.clear(WHITE).unwrap();
+                band.draw_iter([Pixel(corner,BLACK)]).unwrap();
+            }).unwrap();
+            assert_eq!((emulator.bw_ram(),emulator.red_ram()),expected,"{:?} {:?}",rotation,corner);
+        }
+    }
+}
+
+#[test]
+fn band_area_follows_rotation() {
+    let (_emulator,mut spi,mut driver)=setup();
+    let mut areas=Vec::new();
+    let mut strip=([0;50*4],[0;50*4]);
+    driver.draw_banded(&mut spi,(&mut strip.0[..],&mut strip.1[..]),DisplayRotation::Rotate270,|band|areas.push(band.area())).unwrap();
+    assert_eq!(areas.len(),25);
+    assert_eq!(areas[0],Rectangle::new(Point::new(192,0),Size::new(8,200)));
+    assert_eq!(areas[24],Rectangle::new(Point::new(0,0),Size::new(8,200)));
+}
+
+#[test]
+fn display_in_borrowed_buffers_uploads_like_owned() {
+    let mut buffers=Box::new(([0u8;BUFFER_LEN],[0u8;BUFFER_LEN]));
+    let (buffer1,buffer2)=&mut *buffers;
+    let mut borrowed=Display1in54::with_buffers(buffer1,buffer2);
+    let mut owned=Display1in54::new();
+    assert_eq!(borrowed.buffers(),owned.buffers());
+    scene(&mut borrowed);
+    scene(&mut owned);
+    assert_eq!(borrowed.buffers(),owned.buffers());
+
+    let (emulator,mut spi,mut driver)=setup();
+    driver.update_frames(&mut spi,&borrowed).unwrap();
+    assert_eq!(emulator.bw_ram(),owned.buffer1());
+    assert_eq!(emulator.red_ram(),owned.buffer2());
+}
@@ -0,0 +1,223 @@
+use embedded_graphics_core::{
+    geometry::{Dimensions, Point, Size},
+    draw_target::DrawTarget,
+    primitives::Rectangle,
+    Pixel,
+};
+use epd_driver::{
+    color::{BLACK, DGRAY, LGRAY, WHITE},
+    prelude::*,
+    snapshot::Snapshot,
+};
+
+const ROTATIONS:[DisplayRotation;4]=[
+    DisplayRotation::Rotate0,
+    DisplayRotation::Rotate90,
+    DisplayRotation::Rotate180,
+    DisplayRotation::Rotate270,
+];
+
+/// Luma of pixel `x`, `y` of packed rows `stride` bytes apart, read independently of `PackedImage`
+fn packed_luma(data:&[u8],format:PackedFormat,stride:usize,x:usize,y:usize)->u8 {
+    let bits=format.bits_per_pixel();
+    let bit=x*bits;
+    let byte=data[y*stride+bit/8];
+    match format {
+        PackedFormat::Bpp1=>if byte&(0b10000000>>(bit%8))!=0 {3} else {0},
+        PackedFormat::Bpp2=>(byte>>(6-bit%8))&0b11,
+    }
+}
+
+/// A display in `rotation` cleared to `color`
+fn cleared(rotation:DisplayRotation,color:Color)->Display1in54 {
+    let mut display=Display1in54::new();
+    display.set_rotation(rotation);
+    display.clear(color).unwrap();
+    display
+}
+
+#[test]
+fn clear_buffer_writes_the_bits_draw_iter_writes() {
+    for (color,bytes) in [(WHITE,(0xff,0xff)),(LGRAY,(0x00,0xff)),(DGRAY,(0xff,0x00)),(BLACK,(0x00,0x00))].iter() {
+        let mut cleared=Display1in54::new();
+        cleared.clear_buffer(*color);
+        assert!(cleared.buffer1().iter().all(|&byte|byte==bytes.0),"{:?}",color);
+        assert!(cleared.buffer2().iter().all(|&byte|byte==bytes.1),"{:?}",color);
+
+        let mut drawn=Display1in54::new();
+        drawn.clear_buffer(if *color==WHITE {BLACK} else {WHITE});
+        drawn.draw_iter((0..200).flat_map(|y|(0..200).map(move |x|Pixel(Point::new(x,y),*color)))).unwrap();
+        assert_eq!(drawn.buffers(),cleared.buffers(),"{:?}",color);
+    }
+}
+
+#[test]
+fn draw_iter_supports_all_rotations() {
+    for &rotation in ROTATIONS.iter() {
+        let mut display=cleared(rotation,WHITE);
+        display.draw_iter([Pixel(Point::new(0,0),BLACK),Pixel(Point::new(199,5),DGRAY),Pixel(Point::new(200,5),BLACK)]).unwrap();
+        let snapshot=Snapshot::new(&display);
+        assert_eq!((snapshot.luma(0,0),snapshot.luma(199,5),snapshot.luma(1,0)),(0,1,3),"{:?}",rotation);
+        let corner=rotation.to_panel(&Rectangle::new(Point::new(0,0),Size::new(1,1)),Size::new(200,200)).top_left;
+        display.set_rotation(DisplayRotation::Rotate0);
+        assert_eq!(Snapshot::new(&display).luma(corner.x as usize,corner.y as usize),0,"{:?}",rotation);
+    }
+}
+
+#[test]
+fn blit_starts_at_unaligned_columns() {
+    let data=[0b1010_1100,0b1100_0000,0b0000_1111,0b0100_0000];
+    let image=PackedImage::new(&data,PackedFormat::Bpp1,Size::new(10,2));
+    let mut display=cleared(DisplayRotation::Rotate0,BLACK);
+    display.blit(&image,Point::new(3,5));
+    let snapshot=Snapshot::new(&display);
+    for y in 4..8 {
+        for x in 0..16 {
+            let inside=(3..13).contains(&x)&&(5..7).contains(&y);
+            let expected=if inside {packed_luma(&data,PackedFormat::Bpp1,2,x-3,y-5)} else {0};
+            assert_eq!(snapshot.luma(x,y),expected,"pixel ({},{})",x,y);
+        }
+    }
+}
+
+#[test]
+fn blit_leaves_masked_pixels_alone() {
+    let data=[0xff,0xff,0x00,0x00];
+    let mask=[0b1100_1100,0b1000_0000,0b0011_0011,0b0100_0000];
+    let image=PackedImage::new(&data,PackedFormat::Bpp1,Size::new(9,2)).mask(&mask);
+    let mut display=cleared(DisplayRotation::Rotate0,LGRAY);
+    display.blit(&image,Point::new(13,0));
+    let snapshot=Snapshot::new(&display);
+    let row0:Vec<_>=(13..22).map(|x|snapshot.luma(x,0)).collect();
+    let row1:Vec<_>=(13..22).map(|x|snapshot.luma(x,1)).collect();
+    assert_eq!(row0,[3,3,2,2,3,3,2,2,3]);
+    assert_eq!(row1,[2,2,0,0,2,2,0,0,2]);
+    assert_eq!(image.luma(2,0),None);
+    assert_eq!(image.luma(0,0),Some(3));
+}
+
+#[test]
+fn blit_skips_stride_padding() {
+    // one byte of pixels and two bytes of padding per row
+    let data=[0b1111_0000,0xff,0xff,0b0000_1111,0xff,0xff];
+    let image=PackedImage::with_stride(&data,PackedFormat::Bpp1,Size::new(8,2),3);
+    let mut display=cleared(DisplayRotation::Rotate0,BLACK);
+    display.blit(&image,Point::new(4,0));
+    let snapshot=Snapshot::new(&display);
+    let rows:Vec<Vec<_>>=(0..3).map(|y|(0..20).map(|x|snapshot.luma(x,y)).collect()).collect();
+    assert_eq!(rows[0],[0,0,0,0,3,3,3,3,0,0,0,0,0,0,0,0,0,0,0,0]);
+    assert_eq!(rows[1],[0,0,0,0,0,0,0,0,3,3,3,3,0,0,0,0,0,0,0,0]);
+    assert!(rows[2].iter().all(|&luma|luma==0));
+}
+
+#[test]
+fn blit_clips_at_panel_edges() {
+    let data=[0xff;4*2];
+    let image=PackedImage::new(&data,PackedFormat::Bpp2,Size::new(8,4));
+    for &rotation in ROTATIONS.iter() {
+        for &position in [Point::new(-3,-2),Point::new(196,198),Point::new(-10,0),Point::new(200,200)].iter() {
+            let mut display=cleared(rotation,BLACK);
+            display.blit(&image,position);
+            let snapshot=Snapshot::new(&display);
+            for y in 0..200i32 {
+                for x in 0..200i32 {
+                    let inside=(position.x..position.x+8).contains(&x)&&(position.y..position.y+4).contains(&y);
+                    assert_eq!(snapshot.luma(x as usize,y as usize),if inside {3} else {0},"{:?} at {:?}, pixel ({},{})",rotation,position,x,y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn blit_matches_draw_iter_in_all_rotations() {
+    // 2bpp, 5x3 pixels with all four grays, rows padded to a byte boundary
+    let data=[
+        0b00_01_10_11,0b00_000000,
+        0b11_10_01_00,0b11_000000,
+        0b01_11_00_10,0b01_000000,
+    ];
+    let image=PackedImage::new(&data,PackedFormat::Bpp2,Size::new(5,3));
+    for &rotation in ROTATIONS.iter() {
+        for &inverted in [false,true].iter() {
+            for &position in [Point::new(7,9),Point::new(16,0),Point::new(197,198)].iter() {
+                let mut blitted=cleared(rotation,WHITE);
+                let mut drawn=cleared(rotation,WHITE);
+                if inverted {
+                    blitted.invert_display();
+                    drawn.invert_display();
+                }
+                blitted.blit(&image,position);
+                drawn.draw_iter((0..3).flat_map(|y|(0..5).map(move |x|(x,y))).map(|(x,y)|{
+                    let luma=packed_luma(&data,PackedFormat::Bpp2,2,x,y);
+                    Pixel(position+Point::new(x as i32,y as i32),Color::new(luma))
+                })).unwrap();
+                assert_eq!(blitted.buffers(),drawn.buffers(),"{:?} inverted {} at {:?}",rotation,inverted,position);
+            }
+            let mut display=cleared(rotation,WHITE);
+            if inverted {
+                display.invert_display();
+            }
+            display.blit(&image,Point::new(7,9));
+            let snapshot=Snapshot::new(&display);
+            for y in 0..3 {
+                for x in 0..5 {
+                    assert_eq!(snapshot.luma(7+x,9+y),packed_luma(&data,PackedFormat::Bpp2,2,x,y),"{:?} pixel ({},{})",rotation,x,y);
+                }
+            }
+        }
+    }
+}
+
+/// A display implementing only the required methods, delegating to a `Display1in54`
+struct Minimal(Display1in54);
+impl DrawTarget for Minimal {
+    type Color=Color;
+    type Error=core::convert::Infallible;
+    fn draw_iter<I:IntoIterator<Item=Pixel<Color>>>(&mut self,pixels:I)->Result<(),Self::Error> {self.0.draw_iter(pixels)}
+}
+impl Dimensions for Minimal {
+    fn bounding_box(&self)->Rectangle {self.0.bounding_box()}
+}
+impl Display for Minimal {
+    fn clear_buffer(&mut self,color:Color) {self.0.clear_buffer(color)}
+    fn buffer1(&self)->&[u8] {self.0.buffer1()}
+    fn buffer2(&self)->&[u8] {self.0.buffer2()}
+    fn buffers(&self)->(&[u8],&[u8]) {self.0.buffers()}
+    fn set_rotation(&mut self,rotation:DisplayRotation) {self.0.set_rotation(rotation)}
+    fn rotation(&self)->DisplayRotation {self.0.rotation()}
+    fn invert_display(&mut self) {self.0.invert_display()}
+}
+
+#[test]
+fn default_blit_draws_the_visible_pixels() {
+    let data=[0b00_01_10_11,0b01_000000,0b11_10_01_00,0b10_000000];
+    let mask=[0b1110_1000,0b1111_1000];
+    let image=PackedImage::new(&data,PackedFormat::Bpp2,Size::new(5,2)).mask(&mask);
+    for &rotation in ROTATIONS.iter() {
+        let mut minimal=Minimal(cleared(rotation,WHITE));
+        let mut display=cleared(rotation,WHITE);
+        assert!(!minimal.is_inverted());
+        for &position in [Point::new(9,3),Point::new(-2,198)].iter() {
+            minimal.blit(&image,position);
+            display.blit(&image,position);
+        }
+        assert_eq!(minimal.buffers(),display.buffers(),"{:?}",rotation);
+    }
+}
+
+#[test]
+fn panel_display_pads_rows_to_whole_bytes() {
+    let mut display=PanelDisplay::new(122,250);
+    assert_eq!(display.buffer1().len(),16*250);
+    assert_eq!(display.panel_size(),Size::new(122,250));
+    display.draw_iter([Pixel(Point::new(121,1),BLACK),Pixel(Point::new(122,1),BLACK)]).unwrap();
+    assert_eq!(display.buffer1()[16+15],0b10111111);
+    assert_eq!(display.buffer1().iter().filter(|&&byte|byte!=0xff).count(),1);
+
+    display.set_rotation(DisplayRotation::Rotate90);
+    assert_eq!(display.bounding_box().size,Size::new(250,122));
+    display.clear(WHITE).unwrap();
+    display.draw_iter([Pixel(Point::new(0,0),BLACK)]).unwrap();
+    assert_eq!(display.buffer1()[15],0b10111111);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    draw_target::DrawTarget,
+    pixelcolor::{Gray4, GrayColor},
+    primitives::Rectangle,
+};
+use epd_driver::{
+    controller,
+    driver::{Error, LutType},
+    gray16::{Display1in54Gray16, DEFAULT_PASS_FRAMES},
+    prelude::*,
+};
+use common::{setup, setup_controller};
+
+/// 16 bands of 12 rows, black at the top
+fn gray_bands()->Display1in54Gray16 {
+    let mut display=Display1in54Gray16::new();
+    for level in 0..16u8 {
+        let band=Rectangle::new(Point::new(0,level as i32*12),Size::new(200,12));
+        display.fill_solid(&band,Gray4::new(level)).unwrap();
+    }
+    display
+}
+
+#[test]
+fn pixels_are_packed_in_nibbles() {
+    let mut display=Display1in54Gray16::new();
+    assert!(display.buffer().iter().all(|&byte|byte==0xff));
+    display.fill_solid(&Rectangle::new(Point::zero(),Size::new(1,1)),Gray4::new(0x3)).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(1,0),Size::new(1,1)),Gray4::new(0xA)).unwrap();
+    assert_eq!(display.buffer()[0],0x3A);
+    assert_eq!((display.luma(0,0),display.luma(1,0),display.luma(2,0)),(0x3,0xA,0xF));
+
+    display.set_rotation(DisplayRotation::Rotate90);
+    display.clear(Gray4::WHITE).unwrap();
+    display.fill_solid(&Rectangle::new(Point::zero(),Size::new(1,1)),Gray4::new(5)).unwrap();
+    assert_eq!(display.luma(199,0),5);
+    // out of bounds pixels are ignored
+    display.fill_solid(&Rectangle::new(Point::new(-5,195),Size::new(10,10)),Gray4::BLACK).unwrap();
+    assert_eq!(display.luma(4,0),0);
+    assert_eq!(display.luma(5,0),15);
+}
+
+#[test]
+fn passes_add_up_to_luma() {
+    let (emulator,mut spi,mut driver)=setup();
+    let display=gray_bands();
+    emulator.clear_commands();
+    driver.display_gray16(&mut spi,&display,&DEFAULT_PASS_FRAMES).unwrap();
+    assert_eq!(emulator.refreshes(),5);
+
+    // every pass drives LUT1 and LUT3 to VSL (white) in phase A and leaves LUT0 and LUT2 alone
+    for command in emulator.commands().into_iter().filter(|c|c.command==0x32).take(4) {
+        assert_eq!([command.data[0],command.data[12],command.data[24],command.data[36]],[0x00,0x80,0x00,0x80]);
+    }
+    // replay the passes: every custom LUT refresh drives the pixels set in the black/white RAM
+    // for the frames of phase A
+    let mut drive=vec![0u32;200*200];
+    let (mut frames,mut bw,mut control)=(0,vec![0;5000],0);
+    for command in emulator.commands() {
+        match command.command {
+            0x32=>frames=command.data[60] as u32,
+            0x24=>bw=command.data.clone(),
+            0x22=>control=command.data[0],
+            0x20 if control&0x10==0=>{
+                for (i,drive) in drive.iter_mut().enumerate() {
+                    if bw[i/8]&(0x80>>(i%8))!=0 {
+                        *drive+=frames;
+                    }
+                }
+            },
+            _=>{},
+        }
+    }
+    for level in 0..16 {
+        let y=level*12+6;
+        assert!((0..200).all(|x|drive[x+y*200]==level as u32*3),"level {}",level);
+    }
+    let panel=emulator.panel().unwrap();
+    assert_eq!((panel.luma(0,6),panel.luma(0,15*12+6)),(0,3));
+    assert_eq!(driver.current_lut_type(),LutType::Full);
+    assert_eq!(emulator.register(0x32).unwrap()[0],0x80);
+}
+
+#[test]
+fn empty_passes_are_skipped() {
+    let (emulator,mut spi,mut driver)=setup();
+    driver.display_gray16(&mut spi,&gray_bands(),&[0,0,10,20]).unwrap();
+    assert_eq!(emulator.refreshes(),3);
+}
+
+#[test]
+fn other_controllers_are_rejected() {
+    let (emulator,mut spi,mut driver)=setup_controller::<controller::Ssd1675b>();
+    assert_eq!(driver.display_gray16(&mut spi,&gray_bands(),&DEFAULT_PASS_FRAMES),Err(Error::CustomLutUnsupported));
+    assert_eq!(emulator.refreshes(),0);
+    // same LUT layout, but a 176x296 panel and no SSD1681 waveforms
+    let (emulator,mut spi,mut driver)=setup_controller::<controller::Ssd1680>();
+    emulator.clear_commands();
+    assert_eq!(driver.display_gray16(&mut spi,&gray_bands(),&DEFAULT_PASS_FRAMES),Err(Error::CustomLutUnsupported));
+    assert_eq!((emulator.refreshes(),emulator.commands().len()),(0,0));
+}
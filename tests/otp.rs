@@ -0,0 +1,77 @@
+mod common;
+
+use epd_driver::{
+    analog::{OutOfRange, Vcom},
+    driver::{Error, LutType},
+    emulator::Command,
+    otp::{DisplayOption, Irreversible},
+    prelude::*,
+};
+use common::setup;
+
+#[test]
+fn sense_vcom_reads_back_the_measurement() {
+    let (emulator,mut spi,mut driver)=setup();
+    emulator.set_sensed_vcom(0x50);
+    let vcom=driver.otp().sense_vcom(&mut spi,10).unwrap();
+    assert_eq!(vcom.millivolts(),-2_000);
+    assert_eq!(emulator.register(0x29),Some(vec![9]));
+    assert_eq!(emulator.otp_programs(),0);
+}
+
+#[test]
+fn sense_vcom_rejects_bad_durations_and_codes() {
+    let (emulator,mut spi,mut driver)=setup();
+    for &seconds in [0,17].iter() {
+        assert_eq!(driver.otp().sense_vcom(&mut spi,seconds).unwrap_err(),Error::OutOfRange(OutOfRange {min:1,max:16}));
+    }
+    assert_eq!(emulator.register(0x29),None);
+    assert_eq!(driver.otp().sense_vcom(&mut spi,16).unwrap().code(),0x20);
+    assert_eq!(emulator.register(0x29),Some(vec![15]));
+    for &code in [0x04,0x7C,0x09].iter() {
+        emulator.set_sensed_vcom(code);
+        assert_eq!(driver.otp().sense_vcom(&mut spi,1).unwrap_err(),Error::OutOfRange(OutOfRange {min:-3_000,max:-200}),"{:#04x}",code);
+    }
+}
+
+#[test]
+fn program_waveform_only_programs_the_loaded_lut() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    driver.set_lut(&mut spi,LutType::Fast,&mut display).unwrap();
+    let lut=emulator.register(0x32);
+    emulator.set_busy_polls(3);
+    emulator.clear_commands();
+    let start=emulator.elapsed_us();
+    driver.otp().program_waveform(&mut spi,Irreversible::i_understand_otp_writes_are_permanent()).unwrap();
+    assert_eq!(emulator.commands(),[Command {command:0x30,data:vec![]}]);
+    assert_eq!(emulator.register(0x32),lut);
+    assert_eq!(emulator.otp_programs(),1);
+    // waited for BUSY to go low
+    assert!(emulator.elapsed_us()>=start+300);
+}
+
+#[test]
+fn program_vcom_writes_otp() {
+    let (emulator,mut spi,mut driver)=setup();
+    let vcom=Vcom::from_millivolts(-1_200).unwrap();
+    driver.otp().program_vcom(&mut spi,vcom,Irreversible::i_understand_otp_writes_are_permanent()).unwrap();
+    assert_eq!(emulator.otp_vcom(),vcom.code());
+    assert_eq!(emulator.otp_programs(),1);
+}
+
+#[test]
+fn display_option_round_trips() {
+    let (emulator,mut spi,mut driver)=setup();
+    let option=DisplayOption {
+        vcom_selection:0x80,
+        vcom:0,
+        display_mode:[1,2,3,4,5],
+        waveform_version:[6,7,8,9],
+    };
+    let mut otp=driver.otp();
+    otp.program_display_option(&mut spi,&option,Irreversible::i_understand_otp_writes_are_permanent()).unwrap();
+    assert_eq!(emulator.otp_display_option(),[0x80,1,2,3,4,5,6,7,8,9]);
+    let read=otp.read_display_option(&mut spi).unwrap();
+    assert_eq!(read,option);
+}
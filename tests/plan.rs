@@ -0,0 +1,90 @@
+mod common;
+
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    draw_target::DrawTarget,
+    primitives::Rectangle,
+};
+use epd_driver::{
+    color::{BLACK, WHITE},
+    plan::{plan, CostModel, Plan},
+    prelude::*,
+};
+use common::setup;
+
+const PANEL:Size=Size::new(200,200);
+
+fn rect(x:i32,y:i32,width:u32,height:u32)->Rectangle {
+    Rectangle::new(Point::new(x,y),Size::new(width,height))
+}
+
+#[test]
+fn areas_are_aligned_and_clipped() {
+    let planned:Plan<4>=plan(&[rect(3,4,6,2),rect(190,-5,20,10),rect(300,0,5,5)],PANEL,&CostModel::default());
+    assert_eq!(planned.areas(),[rect(0,4,16,2),rect(184,0,16,5)]);
+    assert_eq!(planned.refreshes(),1);
+    assert_eq!(planned.cost(),(2*2*2+32)+(2*5*2+32));
+}
+
+#[test]
+fn empty_input_needs_nothing() {
+    let planned:Plan<4>=plan(&[rect(10,10,0,5),rect(-20,-20,5,5)],PANEL,&CostModel::default());
+    assert!(planned.is_empty());
+    assert_eq!(planned.refreshes(),0);
+}
+
+#[test]
+fn overlapping_and_nearby_areas_are_merged() {
+    let cost=CostModel::default();
+    let planned:Plan<4>=plan(&[rect(0,0,16,16),rect(8,8,16,16)],PANEL,&cost);
+    assert_eq!(planned.areas(),[rect(0,0,24,24)]);
+
+    let planned:Plan<4>=plan(&[rect(0,0,8,8),rect(16,0,8,8)],PANEL,&cost);
+    assert_eq!(planned.areas(),[rect(0,0,24,8)]);
+
+    let planned:Plan<4>=plan(&[rect(0,0,8,8),rect(160,150,8,8)],PANEL,&cost);
+    assert_eq!(planned.areas().len(),2);
+
+    // with free window setup nothing is worth merging
+    let planned:Plan<4>=plan(&[rect(0,0,8,8),rect(16,0,8,8)],PANEL,&CostModel {area:0,..cost});
+    assert_eq!(planned.areas().len(),2);
+}
+
+#[test]
+fn capacity_forces_merges() {
+    let dirty=[rect(0,0,8,8),rect(96,96,8,8),rect(192,192,8,8)];
+    let planned:Plan<2>=plan(&dirty,PANEL,&CostModel::default());
+    assert_eq!(planned.areas().len(),2);
+    for area in dirty {
+        assert!(planned.areas().iter().any(|planned|planned.intersection(&area)==area));
+    }
+}
+
+#[test]
+fn large_changes_upload_the_full_frame() {
+    let cost=CostModel {area:100,..CostModel::default()};
+    let planned:Plan<4>=plan(&[rect(0,0,200,100),rect(0,101,200,99)],PANEL,&cost);
+    assert!(planned.is_full_frame());
+    assert_eq!(planned.cost(),25*200*2+100);
+
+    let planned:Plan<4>=plan(&[rect(0,0,200,100),rect(0,101,200,99)],PANEL,&CostModel::default());
+    assert_eq!(planned.areas().len(),2);
+}
+
+#[test]
+fn execute_uploads_planned_areas() {
+    let (emulator,mut spi,mut driver)=setup();
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    driver.update_frames(&mut spi,&display).unwrap();
+
+    let dirty=[rect(10,10,5,5),rect(100,120,30,4)];
+    for area in dirty {
+        display.fill_solid(&area,BLACK).unwrap();
+    }
+    let planned:Plan<4>=plan(&dirty,PANEL,&CostModel::default());
+    planned.execute(&mut driver,&mut spi,&display).unwrap();
+    assert_eq!(emulator.refreshes(),1);
+    assert_eq!(emulator.bw_ram(),display.buffer1());
+    assert_eq!(emulator.red_ram(),display.buffer2());
+}
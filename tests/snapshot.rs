@@ -0,0 +1,176 @@
+use std::{
+    env,
+    fs,
+    io::ErrorKind,
+    panic,
+    path::{Path, PathBuf},
+    string::String,
+};
+use embedded_graphics_core::{
+    geometry::{Point, Size},
+    draw_target::DrawTarget,
+    primitives::Rectangle,
+    Pixel,
+};
+use epd_driver::{
+    color::{BLACK, DGRAY, LGRAY, WHITE},
+    prelude::*,
+    snapshot::{assert_snapshot, GrayMap, Snapshot, UPDATE_ENV},
+};
+
+/// A scene with all four grays
+fn scene()->Display1in54 {
+    let mut display=Display1in54::new();
+    display.clear(WHITE).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(20,20),Size::new(50,30)),BLACK).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(80,20),Size::new(50,30)),DGRAY).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(140,20),Size::new(50,30)),LGRAY).unwrap();
+    display.fill_solid(&Rectangle::new(Point::new(10,100),Size::new(180,2)),BLACK).unwrap();
+    display
+}
+
+/// A fresh directory for the files written by one test
+fn scratch(name:&str)->PathBuf {
+    let dir=env::temp_dir().join(format!("epd_driver_snapshot_{}_{}",name,std::process::id()));
+    let _=fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs `assert_snapshot` and returns its panic message, if any
+fn check(display:&Display1in54,path:&Path)->Option<String> {
+    panic::catch_unwind(||assert_snapshot(display,path)).err().map(|e|{
+        e.downcast_ref::<String>().cloned().unwrap_or_default()
+    })
+}
+
+#[test]
+fn matches_golden_image() {
+    assert_snapshot(&scene(),"tests/snapshots/scene.png");
+}
+
+#[test]
+fn captures_rotated_coordinates_and_grays() {
+    let mut display=Display1in54::new();
+    display.set_rotation(DisplayRotation::Rotate180);
+    display.clear(WHITE).unwrap();
+    display.draw_iter([Pixel(Point::new(10,20),BLACK),Pixel(Point::new(11,20),DGRAY),Pixel(Point::new(12,20),LGRAY)]).unwrap();
+    let snapshot=Snapshot::new(&display);
+    assert_eq!((snapshot.width(),snapshot.height()),(200,200));
+    assert_eq!([snapshot.luma(10,20),snapshot.luma(11,20),snapshot.luma(12,20),snapshot.luma(13,20)],[0,1,2,3]);
+    // the buffers hold the pixel upside down
+    display.set_rotation(DisplayRotation::Rotate0);
+    assert_eq!(Snapshot::new(&display).luma(189,179),0);
+
+    let mut display=Display1in54::new();
+    display.set_rotation(DisplayRotation::Rotate90);
+    display.clear(WHITE).unwrap();
+    display.blit(&PackedImage::new(&[0b0001_1011],PackedFormat::Bpp2,Size::new(4,1)),Point::new(10,20));
+    let snapshot=Snapshot::new(&display);
+    assert_eq!([snapshot.luma(10,20),snapshot.luma(11,20),snapshot.luma(12,20),snapshot.luma(13,20)],[0,1,2,3]);
+    display.set_rotation(DisplayRotation::Rotate0);
+    assert_eq!(Snapshot::new(&display).luma(179,10),0);
+
+    // inverted buffers, as used for gray4, give the same image
+    let mut inverted=scene();
+    inverted.invert_display();
+    assert_eq!(Snapshot::new(&inverted),Snapshot::new(&scene()));
+}
+
+#[test]
+fn maps_grays() {
+    let snapshot=Snapshot::new(&scene());
+    let gray=snapshot.to_gray8(GrayMap::default());
+    assert_eq!([gray[20*200+20],gray[20*200+80],gray[20*200+140],gray[0]],[0x00,0x55,0xaa,0xff]);
+    let gray=snapshot.to_gray8(GrayMap([10,20,30,40]));
+    assert_eq!([gray[20*200+20],gray[20*200+80],gray[20*200+140],gray[0]],[10,20,30,40]);
+
+    let binary=snapshot.to_binary();
+    assert_eq!([binary.luma(20,20),binary.luma(80,20),binary.luma(140,20),binary.luma(0,0)],[0,0,3,3]);
+}
+
+#[test]
+fn saves_and_loads_png_pgm_and_pbm() {
+    let dir=scratch("formats");
+    let snapshot=Snapshot::new(&scene());
+    for map in [GrayMap::default(),GrayMap([10,20,30,40])].iter() {
+        for name in ["scene.png","scene.pgm"].iter() {
+            let path=dir.join(name);
+            snapshot.save(&path,*map).unwrap();
+            assert_eq!(Snapshot::load(&path,*map).unwrap(),snapshot,"{}",name);
+        }
+    }
+    let path=dir.join("scene.pbm");
+    snapshot.save(&path,GrayMap::default()).unwrap();
+    assert_eq!(Snapshot::load(&path,GrayMap::default()).unwrap(),snapshot.to_binary());
+
+    let pbm=fs::read(&path).unwrap();
+    let header=b"P4\n200 200\n";
+    assert_eq!(&pbm[..header.len()],header);
+    assert_eq!(pbm.len(),header.len()+25*200);
+    // row 20: black from x=20, dark gray (rounded to black) from x=80
+    assert_eq!(pbm[header.len()+20*25+2],0x0f);
+    assert_eq!(pbm[header.len()],0);
+
+    let pgm=dir.join("comment.pgm");
+    fs::write(&pgm,b"P5\n# written by hand\n2 1\n255\n\x00\xff").unwrap();
+    assert_eq!(Snapshot::load(&pgm,GrayMap::default()).unwrap(),Snapshot::from_luma(2,1,vec![0,3]));
+    fs::write(&pgm,b"P2\n2 1\n255\n0 255").unwrap();
+    assert_eq!(Snapshot::load(&pgm,GrayMap::default()).unwrap_err().kind(),ErrorKind::InvalidData);
+    assert_eq!(snapshot.save(dir.join("scene.bmp"),GrayMap::default()).unwrap_err().kind(),ErrorKind::InvalidInput);
+    let _=fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn diff_describes_mismatches() {
+    let expected=Snapshot::new(&scene());
+    assert!(expected.diff(&expected.clone()).is_none());
+
+    let mut display=scene();
+    display.draw_iter([Pixel(Point::new(30,40),WHITE),Pixel(Point::new(32,41),LGRAY)]).unwrap();
+    let diff=expected.diff(&Snapshot::new(&display)).unwrap();
+    assert_eq!(diff.pixels(),2);
+    assert_eq!(diff.bounds(),(30,40,32,41));
+    let text=diff.to_string();
+    assert!(text.starts_with("2 pixels differ between (30, 40) and (32, 41)\n"),"{}",text);
+    assert!(text.contains("  40 0>3 .  . \n"),"{}",text);
+    assert!(text.contains("  41  .  . 0>2\n"),"{}",text);
+
+    let diff=expected.diff(&Snapshot::from_luma(2,1,vec![0,3])).unwrap();
+    assert_eq!(diff.to_string(),"expected a 200x200 image, got 2x1");
+}
+
+#[test]
+fn assert_snapshot_writes_and_compares_golden_images() {
+    let dir=scratch("golden");
+    let golden=dir.join("golden.png");
+    let actual=dir.join("golden.actual.png");
+
+    let message=check(&scene(),&golden).expect("missing golden image accepted");
+    assert!(message.contains("does not exist"),"{}",message);
+    assert!(!golden.exists());
+    assert_eq!(Snapshot::load(&actual,GrayMap::default()).unwrap(),Snapshot::new(&scene()));
+    fs::remove_file(&actual).unwrap();
+
+    env::set_var(UPDATE_ENV,"1");
+    assert_snapshot(&scene(),&golden);
+    assert_snapshot(&scene(),dir.join("golden.pbm"));
+    env::remove_var(UPDATE_ENV);
+    assert_eq!(Snapshot::load(&golden,GrayMap::default()).unwrap(),Snapshot::new(&scene()));
+    assert!(check(&scene(),&golden).is_none());
+    assert!(!actual.exists());
+
+    let mut changed=scene();
+    changed.draw_iter([Pixel(Point::new(30,40),WHITE)]).unwrap();
+    let message=check(&changed,&golden).expect("mismatch accepted");
+    assert!(message.contains("does not match"),"{}",message);
+    assert!(message.contains("1 pixels differ between (30, 40) and (30, 40)"),"{}",message);
+    assert_eq!(Snapshot::load(&actual,GrayMap::default()).unwrap(),Snapshot::new(&changed));
+
+    // PBM golden images only compare black and white
+    let mut lighter=scene();
+    lighter.fill_solid(&Rectangle::new(Point::new(140,20),Size::new(50,30)),WHITE).unwrap();
+    assert!(check(&lighter,&dir.join("golden.pbm")).is_none());
+    assert!(check(&changed,&dir.join("golden.pbm")).is_some());
+    let _=fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::string::{String, ToString};
+use epd_driver::{
+    config::{Border, Config},
+    emulator::Emulator,
+    trace::{decode, Event, TraceSpi},
+};
+use common::init;
+
+#[test]
+fn decodes_init_sequence() {
+    let emulator=Emulator::new();
+    let mut spi=TraceSpi::<_,32>::new(emulator.spi());
+    let mut driver=init(&emulator,&mut spi,Config::default());
+    driver.clear_frame1(&mut spi).unwrap();
+    let lines:Vec<String>=decode(spi.events()).map(|d|d.to_string()).collect();
+    assert_eq!(lines,[
+        "SW_RESET",
+        "DRIVER_CONTROL mux=200 gd=0 sm=0 tb=0",
+        "SET_RAMXPOS start=0 end=24",
+        "SET_RAMYPOS start=0 end=199",
+        "SET_RAMX_COUNTER x=0",
+        "SET_RAMY_COUNTER y=0",
+        "BORDER_WAVEFORM_CONTROL BORDER_WAVEFORM_FOLLOW_LUT|BORDER_WAVEFORM_LUT1",
+        "DATA_ENTRY_MODE DATA_ENTRY_INCRY|DATA_ENTRY_INCRX",
+        "TEMP_CONTROL INTERNAL_TEMP_SENSOR",
+        "SET_RAMXPOS start=0 end=24",
+        "SET_RAMYPOS start=0 end=199",
+        "SET_RAMX_COUNTER x=0",
+        "SET_RAMY_COUNTER y=0",
+        "WRITE_BUFFER1_DATA len=5000 [ff ff ff ff ff ff ff ff ..]",
+    ]);
+}
+
+#[test]
+fn decodes_every_border() {
+    let emulator=Emulator::new();
+    let mut spi=TraceSpi::<_,2>::new(emulator.spi());
+    let mut driver=init(&emulator,&mut spi,Config::default());
+    for (border,name) in [
+        (Border::FollowLut,"BORDER_WAVEFORM_FOLLOW_LUT|BORDER_WAVEFORM_LUT1"),
+        (Border::Lut(3),"BORDER_WAVEFORM_FOLLOW_LUT|BORDER_WAVEFORM_LUT3"),
+        (Border::LutVcomOnRed(0),"BORDER_WAVEFORM_LUT0"),
+        (Border::White,"BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSL"),
+        (Border::Black,"BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSH1"),
+        (Border::Red,"BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSH2"),
+        (Border::Vss,"BORDER_WAVEFORM_FIX_LEVEL|BORDER_WAVEFORM_VSS"),
+        (Border::Vcom,"BORDER_WAVEFORM_VCOM"),
+        (Border::Floating,"BORDER_WAVEFORM_HIZ"),
+    ] {
+        spi.clear();
+        driver.set_border(&mut spi,border).unwrap();
+        let lines:Vec<String>=decode(spi.events()).map(|d|d.to_string()).collect();
+        assert_eq!(lines,[format!("BORDER_WAVEFORM_CONTROL {}",name)]);
+    }
+}
+
+#[test]
+fn ring_buffer_keeps_latest_events() {
+    let emulator=Emulator::new();
+    let mut spi=TraceSpi::<_,4>::new(emulator.spi());
+    let mut driver=init(&emulator,&mut spi,Config::default());
+    driver.display_frame(&mut spi).unwrap();
+    assert_eq!(spi.events().count(),4);
+    assert!(spi.dropped()>0);
+    let last:Vec<String>=decode(spi.events()).map(|d|d.to_string()).collect();
+    assert_eq!(last,["<data> len=2 [00 00]","UPDATE_DISPLAY_CTRL2 BW_DISPLAY_MODE_1","MASTER_ACTIVATE"]);
+}
+
+#[test]
+fn dump_round_trips() {
+    let emulator=Emulator::new();
+    let mut spi=TraceSpi::<_,32>::new(emulator.spi());
+    init(&emulator,&mut spi,Config::default());
+    let mut bytes=[0;512];
+    let len=spi.dump(&mut bytes);
+    let mut parsed=Vec::new();
+    let mut rest=&bytes[..len];
+    while let Some((event,used))=Event::parse(rest) {
+        parsed.push(event);
+        rest=&rest[used..];
+    }
+    assert_eq!(parsed,spi.events().collect::<Vec<_>>());
+}